@@ -0,0 +1,253 @@
+use std::path::Path;
+
+use strum::Display;
+
+use crate::atlas_models::{MigrateApply, SummaryReport};
+use crate::util::{file_path, line_col, local_dir};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum AnnotationLevel {
+    #[strum(serialize = "error")]
+    Error,
+
+    #[strum(serialize = "warning")]
+    Warning,
+
+    #[strum(serialize = "notice")]
+    Notice,
+}
+
+/// A GitHub Actions workflow command that annotates a file, e.g.
+/// `::error file=migrations/1.sql,line=3,col=1,title=DS102::Dropping table`.
+/// `Display` renders the command, escaped so it can be printed as is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Annotation {
+    pub level: AnnotationLevel,
+    pub file: Option<String>,
+    pub line: Option<usize>,
+    pub col: Option<usize>,
+    pub title: Option<String>,
+    pub message: String,
+}
+impl Annotation {
+    /// A warning for each lint diagnostic and an error for each file or step
+    /// that could not be analyzed.
+    pub fn from_summary_report(report: &SummaryReport) -> Vec<Self> {
+        let dir = local_dir(&report.env.dir);
+        let mut annotations = Vec::new();
+
+        for file in &report.files {
+            let path = file_path(dir.as_deref(), &file.name);
+
+            for r in &file.reports {
+                for d in &r.diagnostics {
+                    let pos = line_col(&file.text, usize::try_from(d.pos).unwrap_or(0));
+
+                    annotations.push(Annotation {
+                        level: AnnotationLevel::Warning,
+                        file: Some(path.clone()),
+                        line: pos.map(|(line, _)| line),
+                        col: pos.map(|(_, col)| col),
+                        title: Some(d.code.clone()).filter(|c| !c.is_empty()),
+                        message: format!("{}: {}", r.text, d.text),
+                    });
+                }
+            }
+
+            if !file.error.is_empty() {
+                annotations.push(Annotation {
+                    level: AnnotationLevel::Error,
+                    file: Some(path.clone()),
+                    line: None,
+                    col: None,
+                    title: None,
+                    message: file.error.clone(),
+                });
+            }
+        }
+
+        for step in &report.steps {
+            if !step.error.is_empty() {
+                annotations.push(Annotation {
+                    level: AnnotationLevel::Error,
+                    file: None,
+                    line: None,
+                    col: None,
+                    title: Some(step.name.clone()).filter(|n| !n.is_empty()),
+                    message: step.error.clone(),
+                });
+            }
+        }
+
+        annotations
+    }
+
+    /// An error for each migration file that failed to apply. If `dir` is
+    /// the local migration directory, the annotation points at the line of
+    /// the failing statement.
+    pub fn from_migrate_apply(apply: &MigrateApply, dir: Option<&Path>) -> Vec<Self> {
+        let mut annotations = Vec::new();
+
+        for file in &apply.applied {
+            let err = match file.error {
+                Some(ref err) => err,
+                None => continue,
+            };
+
+            let path = match dir {
+                Some(dir) => dir.join(&file.file.name).to_string_lossy().into_owned(),
+                None => file.file.name.clone(),
+            };
+
+            annotations.push(Annotation {
+                level: AnnotationLevel::Error,
+                line: dir.and_then(|dir| statement_line(&dir.join(&file.file.name), &err.sql)),
+                file: Some(path),
+                col: None,
+                title: Some(format!("failed to apply {}", file.file.name)),
+                message: format!("{}\n\n{}", err.error, err.sql),
+            });
+        }
+
+        let file_failed = apply.applied.iter().any(|f| f.error.is_some());
+        if !apply.error.is_empty() && !file_failed {
+            annotations.push(Annotation {
+                level: AnnotationLevel::Error,
+                file: None,
+                line: None,
+                col: None,
+                title: Some("migrate apply".into()),
+                message: apply.error.clone(),
+            });
+        }
+
+        annotations
+    }
+}
+impl std::fmt::Display for Annotation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut props = Vec::new();
+
+        if let Some(ref file) = self.file {
+            props.push(format!("file={}", escape_property(file)));
+        }
+
+        if let Some(line) = self.line {
+            props.push(format!("line={}", line));
+        }
+
+        if let Some(col) = self.col {
+            props.push(format!("col={}", col));
+        }
+
+        if let Some(ref title) = self.title {
+            props.push(format!("title={}", escape_property(title)));
+        }
+
+        write!(f, "::{}", self.level)?;
+
+        if !props.is_empty() {
+            write!(f, " {}", props.join(","))?;
+        }
+
+        write!(f, "::{}", escape_data(&self.message))
+    }
+}
+
+/// Renders annotations one workflow command per line.
+pub fn render(annotations: &[Annotation]) -> String {
+    annotations
+        .iter()
+        .map(|a| format!("{}\n", a))
+        .collect::<String>()
+}
+
+/// The 1-based line `sql` starts on in the file at `path`.
+fn statement_line(path: &Path, sql: &str) -> Option<usize> {
+    let sql = sql.trim();
+    if sql.is_empty() {
+        return None;
+    }
+
+    let text = std::fs::read_to_string(path).ok()?;
+    let pos = text.find(sql)?;
+
+    line_col(&text, pos).map(|(line, _)| line)
+}
+
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply() -> MigrateApply {
+        serde_json::from_str(include_str!("../testdata/apply_error.json")).unwrap()
+    }
+
+    #[test]
+    fn from_summary_report_golden() {
+        let report: SummaryReport =
+            serde_json::from_str(include_str!("../testdata/lint.json")).unwrap();
+
+        assert_eq!(
+            render(&Annotation::from_summary_report(&report)),
+            include_str!("../testdata/lint.annotations.txt")
+        );
+    }
+
+    #[test]
+    fn from_migrate_apply_golden() {
+        assert_eq!(
+            render(&Annotation::from_migrate_apply(&apply(), None)),
+            include_str!("../testdata/apply_error.annotations.txt")
+        );
+    }
+
+    #[test]
+    fn from_migrate_apply_finds_statement_line() {
+        let dir =
+            std::env::temp_dir().join(format!("atlas_exec_annotations_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("20240510140000_add_age.sql"),
+            "ALTER TABLE \"users\" ADD COLUMN \"age\" int NULL;\n\n-- backfilled by the app\nALTER TABLE \"users\"\n  ALTER COLUMN \"age\" SET NOT NULL;\n",
+        )
+        .unwrap();
+
+        let annotations = Annotation::from_migrate_apply(&apply(), Some(&dir));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0].line, Some(4));
+        assert_eq!(
+            annotations[0].file.as_deref(),
+            dir.join("20240510140000_add_age.sql").to_str()
+        );
+    }
+
+    #[test]
+    fn escapes_data_and_properties() {
+        let annotation = Annotation {
+            level: AnnotationLevel::Notice,
+            file: Some("migrations/a,b.sql".into()),
+            line: Some(3),
+            col: None,
+            title: Some("cast: '0'::int".into()),
+            message: "100% of rows\r\nuse '0'::int, not ::text".into(),
+        };
+
+        assert_eq!(
+            annotation.to_string(),
+            "::notice file=migrations/a%2Cb.sql,line=3,title=cast%3A '0'%3A%3Aint::100%25 of rows%0D%0Ause '0'::int, not ::text"
+        );
+    }
+}
@@ -0,0 +1,406 @@
+use anyhow::anyhow;
+use thiserror::Error;
+
+use std::path::Path;
+
+use crate::atlas::{Client, SchemaApplyParams, SchemaPlanParams};
+use crate::atlas_models::{SchemaApply, SchemaPlan};
+use crate::util::{local_dir, NonEmptyString};
+
+/// A reviewed `schema apply`: the parameters it runs with and the plan atlas
+/// saved for them. Created by [`Client::plan_schema_apply`] and consumed by
+/// [`Client::execute_schema_apply`], which applies the saved plan itself, so
+/// nothing but the approved statements can run.
+///
+/// Needs an atlas that can save plans with `schema plan --save` and apply
+/// them with `schema apply --plan file://...`. Both are Atlas Pro features,
+/// so the CLI must be logged in (`atlas login`) before planning.
+#[derive(Debug, Clone)]
+pub struct SchemaApplyPlan {
+    params: SchemaApplyParams,
+    plan: SchemaPlan,
+}
+impl SchemaApplyPlan {
+    /// The statements that will run if the plan is executed.
+    pub fn pending(&self) -> Vec<String> {
+        match self.plan.file {
+            Some(ref file) => file.stmts.iter().map(|s| s.text.clone()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plan.file.as_ref().is_none_or(|f| f.stmts.is_empty())
+    }
+
+    /// The plan as atlas saved it, including the hashes of the schema states
+    /// it moves between.
+    pub fn schema_plan(&self) -> &SchemaPlan {
+        &self.plan
+    }
+
+    /// The url of the saved plan file, relative to the working dir of the
+    /// client, e.g. `file://20240510120000.plan.hcl`.
+    pub fn plan_url(&self) -> Option<String> {
+        let file = self.plan.file.as_ref()?;
+
+        if file.url.starts_with("file://") {
+            return Some(file.url.clone());
+        }
+
+        match file.name.ends_with(".plan.hcl") {
+            true => Some(format!("file://{}", file.name)),
+            false => Some(format!("file://{}.plan.hcl", file.name)),
+        }
+    }
+}
+
+/// The database no longer matches the plan: applying it now would run
+/// statements nobody approved.
+#[derive(Debug, Error)]
+#[error("{}", self.err_string())]
+pub struct PlanMismatchError {
+    pub approved: Vec<String>,
+    pub actual: Vec<String>,
+    /// Whether `actual` was already applied, i.e. the database changed
+    /// between the final check and the apply itself.
+    pub applied: bool,
+}
+impl PlanMismatchError {
+    pub fn new(approved: Vec<String>, actual: Vec<String>, applied: bool) -> Self {
+        Self {
+            approved,
+            actual,
+            applied,
+        }
+    }
+
+    pub fn err_string(&self) -> String {
+        let what = match self.applied {
+            true => "applied statements differ from",
+            false => "pending statements no longer match",
+        };
+
+        format!(
+            "{} the approved plan: approved {} statement(s), got {}:\n{}",
+            what,
+            self.approved.len(),
+            self.actual.len(),
+            self.actual.join("\n")
+        )
+    }
+}
+
+impl Client {
+    /// Plans `schema apply` with `schema plan --save` and returns the plan
+    /// for review. The `dry_run` and `plan` fields of `params` are ignored.
+    /// The plan file is saved in the working dir of the client and removed
+    /// once the plan is executed or rejected.
+    pub fn plan_schema_apply(&self, params: SchemaApplyParams) -> anyhow::Result<SchemaApplyPlan> {
+        let plan = self.schema_plan(SchemaPlanParams {
+            env: params.env.clone(),
+            config_url: params.config_url.clone(),
+            from: params.url.iter().cloned().collect(),
+            to: params.to.iter().cloned().collect(),
+            dev_url: params.dev_url.clone(),
+            schema: params.schema.clone(),
+            exclude: params.exclude.clone(),
+            save: true,
+            vars: params.vars.clone(),
+            ..Default::default()
+        })?;
+
+        Ok(SchemaApplyPlan { params, plan })
+    }
+
+    /// Applies a plan. A dry run is repeated first and nothing is applied
+    /// unless it plans exactly the approved statements. The apply itself
+    /// runs the saved plan with `--plan`: atlas refuses it if the database
+    /// no longer is in the state the plan was made from, so a change that
+    /// slips in after the dry run cannot cause unapproved statements to run.
+    /// The applied statements are compared with the plan once more, and a
+    /// [`PlanMismatchError`] with `applied` set reports any difference.
+    ///
+    /// The plan file is removed afterwards, unless a [`PlanMismatchError`]
+    /// is returned: it is then kept so the approved plan can be compared
+    /// with the database.
+    pub fn execute_schema_apply(&self, plan: SchemaApplyPlan) -> anyhow::Result<SchemaApply> {
+        let result = self.apply_plan(&plan);
+
+        let mismatch = result.as_ref().is_err_and(|e| e.is::<PlanMismatchError>());
+
+        if !mismatch {
+            if let Some(url) = plan.plan_url() {
+                self.remove_plan_file(&url);
+            }
+        }
+
+        result
+    }
+
+    fn apply_plan(&self, plan: &SchemaApplyPlan) -> anyhow::Result<SchemaApply> {
+        let check = self.schema_apply(SchemaApplyParams {
+            dry_run: true,
+            plan: None,
+            ..plan.params.clone()
+        })?;
+
+        if !same_statements(&check.changes.pending, &plan.pending()) {
+            return Err(
+                PlanMismatchError::new(plan.pending(), check.changes.pending, false).into(),
+            );
+        }
+
+        let url = match plan.plan_url() {
+            Some(url) if !plan.is_empty() => url,
+            _ => return Ok(check),
+        };
+
+        let result = self.schema_apply(SchemaApplyParams {
+            dry_run: false,
+            plan: Some(NonEmptyString::new(&url)?),
+            ..plan.params.clone()
+        })?;
+
+        check_applied(plan.pending(), &result)?;
+        Ok(result)
+    }
+
+    /// Asks `approve` whether to apply the plan and executes it if so. A
+    /// rejected plan is an error, nothing is applied and the plan file is
+    /// removed.
+    pub fn execute_schema_apply_with_approval<F>(
+        &self,
+        plan: SchemaApplyPlan,
+        approve: F,
+    ) -> anyhow::Result<SchemaApply>
+    where
+        F: FnOnce(&SchemaApplyPlan) -> bool,
+    {
+        if !plan.is_empty() && !approve(&plan) {
+            if let Some(url) = plan.plan_url() {
+                self.remove_plan_file(&url);
+            }
+
+            return Err(anyhow!("schema apply plan was not approved"));
+        }
+
+        self.execute_schema_apply(plan)
+    }
+
+    pub(crate) fn remove_plan_file(&self, url: &str) {
+        let path = match local_dir(url) {
+            Some(path) => path,
+            None => return,
+        };
+
+        let path = match self.working_dir() {
+            Some(wd) => Path::new(wd).join(path),
+            None => Path::new(&path).to_path_buf(),
+        };
+
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+/// Checks that an apply of the saved plan executed the approved `pending`
+/// statements: all of them, or, if one failed, those up to and including the
+/// one that failed.
+fn check_applied(pending: Vec<String>, result: &SchemaApply) -> Result<(), PlanMismatchError> {
+    let mut executed = result.changes.applied.clone();
+    if let Some(ref err) = result.changes.error {
+        executed.push(err.stmt.clone());
+    }
+
+    let prefix = pending.get(..executed.len()).unwrap_or(&pending);
+    if !same_statements(prefix, &executed)
+        || (result.changes.error.is_none() && executed.len() != pending.len())
+    {
+        return Err(PlanMismatchError::new(pending, executed, true));
+    }
+
+    Ok(())
+}
+
+/// Compares statements the way atlas prints them in plans and apply results,
+/// which may differ in surrounding whitespace and the closing semicolon.
+fn same_statements(a: &[String], b: &[String]) -> bool {
+    let normalize = |s: &String| s.trim().trim_end_matches(';').trim_end().to_string();
+
+    a.len() == b.len() && a.iter().map(normalize).eq(b.iter().map(normalize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // schema plan --save --format '{{ json . }}'
+    const SCHEMA_PLAN: &str = r#"{
+        "Env": {"Driver": "postgres", "URL": {"Scheme": "postgres", "Host": "localhost:5432", "Path": "/app"}},
+        "File": {
+            "Name": "20240510120000",
+            "FromHash": "oVb8oGRvW0iNHv0VRWGbXDLfFJrvr4QwjIa0O6ds9ZA=",
+            "ToHash": "pSm2u6/mEQOwbhjY7mObA3ljwY1u3ePVyY5IGb3Ml/o=",
+            "Migration": "ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;\nCREATE INDEX \"users_email\" ON \"users\" (\"email\");\n",
+            "Stmts": [
+                {"Pos": 0, "Text": "ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;"},
+                {"Pos": 52, "Text": "CREATE INDEX \"users_email\" ON \"users\" (\"email\");"}
+            ],
+            "URL": "file://20240510120000.plan.hcl"
+        }
+    }"#;
+
+    fn plan(json: &str) -> SchemaApplyPlan {
+        SchemaApplyPlan {
+            params: SchemaApplyParams::default(),
+            plan: serde_json::from_str(json).unwrap(),
+        }
+    }
+
+    fn schema_apply(json: &str) -> SchemaApply {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn strings(s: &[&str]) -> Vec<String> {
+        s.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn same_statements_ignores_whitespace_and_semicolons() {
+        let tests = [
+            (vec!["SELECT 1;"], vec!["SELECT 1"], true),
+            (vec!["  SELECT 1 ;\n"], vec!["SELECT 1;"], true),
+            (
+                vec!["SELECT 1;", "SELECT 2;"],
+                vec!["SELECT 1", "SELECT 2"],
+                true,
+            ),
+            (vec![], vec![], true),
+            (vec!["SELECT 1;"], vec!["SELECT  1;"], false),
+            (vec!["SELECT 1;"], vec!["select 1;"], false),
+            (
+                vec!["SELECT 1;", "SELECT 2;"],
+                vec!["SELECT 2;", "SELECT 1;"],
+                false,
+            ),
+            (vec!["SELECT 1;"], vec!["SELECT 1;", "SELECT 2;"], false),
+        ];
+
+        for (a, b, want) in tests {
+            assert_eq!(
+                same_statements(&strings(&a), &strings(&b)),
+                want,
+                "{:?} {:?}",
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn check_applied_against_plan() {
+        let pending = plan(SCHEMA_PLAN).pending();
+
+        // schema apply --plan file://20240510120000.plan.hcl --format '{{ json . }}'
+        let applied = schema_apply(
+            r#"{"Driver": "postgres", "Changes": {"Applied": [
+                "ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL",
+                "CREATE INDEX \"users_email\" ON \"users\" (\"email\")"
+            ]}}"#,
+        );
+        assert!(check_applied(pending.clone(), &applied).is_ok());
+
+        // the second statement failed, the executed statements are a prefix
+        let failed = schema_apply(
+            r#"{"Driver": "postgres", "Changes": {
+                "Applied": ["ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL"],
+                "Error": {"Stmt": "CREATE INDEX \"users_email\" ON \"users\" (\"email\")", "Text": "pq: relation \"users_email\" already exists"}
+            }, "Error": "pq: relation \"users_email\" already exists"}"#,
+        );
+        assert!(check_applied(pending.clone(), &failed).is_ok());
+
+        // only part of the plan ran, without an error
+        let partial = schema_apply(
+            r#"{"Changes": {"Applied": ["ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL"]}}"#,
+        );
+        let err = check_applied(pending.clone(), &partial).unwrap_err();
+        assert!(err.applied);
+        assert_eq!(err.approved, pending);
+        assert_eq!(err.actual.len(), 1);
+
+        // a statement that was never approved
+        let other = schema_apply(
+            r#"{"Changes": {"Applied": ["DROP TABLE \"users\"", "CREATE INDEX \"users_email\" ON \"users\" (\"email\")"]}}"#,
+        );
+        let err = check_applied(pending.clone(), &other).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("applied statements differ from the approved plan: approved 2 statement(s), got 2:\nDROP TABLE"));
+
+        // more statements than approved
+        let extra = schema_apply(
+            r#"{"Changes": {"Applied": [
+                "ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL",
+                "CREATE INDEX \"users_email\" ON \"users\" (\"email\")",
+                "DROP TABLE \"users\""
+            ]}}"#,
+        );
+        assert!(check_applied(pending, &extra).is_err());
+    }
+
+    #[test]
+    fn plan_urls() {
+        let p = plan(SCHEMA_PLAN);
+        assert_eq!(
+            p.plan_url().as_deref(),
+            Some("file://20240510120000.plan.hcl")
+        );
+        assert_eq!(p.pending().len(), 2);
+        assert!(!p.is_empty());
+
+        let p = plan(
+            r#"{"Env": {}, "File": {"Name": "20240510120000", "URL": "atlas://app/plans/20240510120000"}}"#,
+        );
+        assert_eq!(
+            p.plan_url().as_deref(),
+            Some("file://20240510120000.plan.hcl")
+        );
+        assert!(p.is_empty());
+
+        let p = plan(r#"{"Env": {}, "File": {"Name": "20240510120000.plan.hcl"}}"#);
+        assert_eq!(
+            p.plan_url().as_deref(),
+            Some("file://20240510120000.plan.hcl")
+        );
+
+        let p = plan(r#"{"Env": {}}"#);
+        assert_eq!(p.plan_url(), None);
+        assert!(p.pending().is_empty());
+        assert!(p.is_empty());
+    }
+
+    #[test]
+    fn rejected_plan_is_not_applied() {
+        let dir = std::env::temp_dir().join(format!("atlas_exec_approval_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let plan_file = dir.join("20240510120000.plan.hcl");
+        std::fs::write(&plan_file, "plan \"20240510120000\" {}\n").unwrap();
+
+        // `false` stands in for atlas, it must not be called
+        let client = Client::new(dir.to_str(), "false").unwrap();
+
+        let mut seen = Vec::new();
+        let err = client
+            .execute_schema_apply_with_approval(plan(SCHEMA_PLAN), |p| {
+                seen = p.pending();
+                false
+            })
+            .unwrap_err();
+
+        assert_eq!(err.to_string(), "schema apply plan was not approved");
+        assert_eq!(seen.len(), 2);
+        assert!(!plan_file.exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
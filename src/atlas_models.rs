@@ -1,22 +1,40 @@
+use crate::database_url::DatabaseUrl;
+use crate::util::line_col;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use thiserror::Error;
-use time::{
-    macros::{date, time},
-    Duration, PrimitiveDateTime,
-};
+use time::{macros::datetime, Duration, OffsetDateTime};
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct File {
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub name: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub version: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub description: String,
 }
+impl File {
+    /// Builds a File from a migration file name such as
+    /// `20240510120000_create_users.sql`, splitting the version from the
+    /// description the same way atlas does.
+    pub fn from_name(name: &str) -> Self {
+        let stem = name.strip_suffix(".sql").unwrap_or(name);
+        let (version, description) = match stem.split_once('_') {
+            Some((version, description)) => (version, description),
+            None => (stem, ""),
+        };
+
+        Self {
+            name: name.into(),
+            version: version.into(),
+            description: description.into(),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
@@ -24,11 +42,11 @@ pub struct AppliedFile {
     #[serde(flatten)]
     pub file: File,
 
-    #[serde(default = "default_time")]
-    pub start: PrimitiveDateTime,
+    #[serde(default = "default_time", with = "go_time")]
+    pub start: OffsetDateTime,
 
-    #[serde(default = "default_time")]
-    pub end: PrimitiveDateTime,
+    #[serde(default = "default_time", with = "go_time")]
+    pub end: OffsetDateTime,
 
     pub skipped: isize,
 
@@ -43,11 +61,11 @@ pub struct RevertedFile {
     #[serde(flatten)]
     pub file: File,
 
-    #[serde(default = "default_time")]
-    pub start: PrimitiveDateTime,
+    #[serde(default = "default_time", with = "go_time")]
+    pub start: OffsetDateTime,
 
-    #[serde(default = "default_time")]
-    pub end: PrimitiveDateTime,
+    #[serde(default = "default_time", with = "go_time")]
+    pub end: OffsetDateTime,
 
     pub skipped: isize,
 
@@ -61,110 +79,189 @@ pub struct RevertedFile {
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MigrateApply {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub pending: Vec<File>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub applied: Vec<AppliedFile>,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub current: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub target: String,
 
-    #[serde(default = "default_time")]
-    pub start: PrimitiveDateTime,
+    #[serde(default = "default_time", with = "go_time")]
+    pub start: OffsetDateTime,
 
-    #[serde(default = "default_time")]
-    pub end: PrimitiveDateTime,
+    #[serde(default = "default_time", with = "go_time")]
+    pub end: OffsetDateTime,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub error: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MigrateDown {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub planned: Vec<File>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub reverted: Vec<RevertedFile>,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub current: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub target: String,
 
-    #[serde(skip_serializing_if = "isize_is_zero")]
+    #[serde(default, skip_serializing_if = "isize_is_zero")]
     pub total: isize,
 
-    #[serde(default = "default_time")]
-    pub start: PrimitiveDateTime,
+    #[serde(default = "default_time", with = "go_time")]
+    pub start: OffsetDateTime,
 
-    #[serde(default = "default_time")]
-    pub end: PrimitiveDateTime,
+    #[serde(default = "default_time", with = "go_time")]
+    pub end: OffsetDateTime,
 
-    #[serde(rename = "URL", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "URL", default, skip_serializing_if = "String::is_empty")]
     pub url: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
-    pub status: String,
+    #[serde(
+        default,
+        deserialize_with = "empty_status_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub status: Option<MigrationStatus>,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub error: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct MigrateStatus {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub available: Vec<File>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub pending: Vec<File>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub applied: Vec<Revision>,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub current: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub next: String,
 
-    #[serde(skip_serializing_if = "isize_is_zero")]
+    #[serde(default, skip_serializing_if = "isize_is_zero")]
     pub count: isize,
 
-    #[serde(skip_serializing_if = "isize_is_zero")]
+    #[serde(default, skip_serializing_if = "isize_is_zero")]
     pub total: isize,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
-    pub status: String,
+    #[serde(
+        default,
+        deserialize_with = "empty_status_as_none",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub status: Option<MigrationStatus>,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub error: String,
 
-    #[serde(rename = "SQL", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "SQL", default, skip_serializing_if = "String::is_empty")]
     pub sql: String,
 }
 
+/// The status atlas reports for `migrate status` and `migrate down`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(from = "String", into = "String")]
+pub enum MigrationStatus {
+    /// All migrations are applied.
+    Ok,
+    /// Migrations are waiting to be applied.
+    Pending,
+    /// The plan must be approved in Atlas Cloud before it runs.
+    PendingUser,
+    Approved,
+    Aborted,
+    Failed,
+    /// A status this crate does not know yet.
+    Unknown(String),
+}
+impl MigrationStatus {
+    pub fn is_pending(&self) -> bool {
+        matches!(
+            self,
+            MigrationStatus::Pending | MigrationStatus::PendingUser
+        )
+    }
+
+    pub fn needs_approval(&self) -> bool {
+        *self == MigrationStatus::PendingUser
+    }
+
+    pub fn is_failed(&self) -> bool {
+        matches!(self, MigrationStatus::Failed | MigrationStatus::Aborted)
+    }
+}
+impl From<&str> for MigrationStatus {
+    fn from(s: &str) -> Self {
+        match s {
+            "OK" => MigrationStatus::Ok,
+            "PENDING" => MigrationStatus::Pending,
+            "PENDING_USER" => MigrationStatus::PendingUser,
+            "APPROVED" => MigrationStatus::Approved,
+            "ABORTED" => MigrationStatus::Aborted,
+            "FAILED" => MigrationStatus::Failed,
+            s => MigrationStatus::Unknown(s.to_string()),
+        }
+    }
+}
+impl From<String> for MigrationStatus {
+    fn from(s: String) -> Self {
+        MigrationStatus::from(s.as_str())
+    }
+}
+impl From<MigrationStatus> for String {
+    fn from(status: MigrationStatus) -> Self {
+        status.to_string()
+    }
+}
+impl std::fmt::Display for MigrationStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            MigrationStatus::Ok => "OK",
+            MigrationStatus::Pending => "PENDING",
+            MigrationStatus::PendingUser => "PENDING_USER",
+            MigrationStatus::Approved => "APPROVED",
+            MigrationStatus::Aborted => "ABORTED",
+            MigrationStatus::Failed => "FAILED",
+            MigrationStatus::Unknown(s) => s,
+        };
+
+        write!(f, "{}", s)
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct SummaryReport {
-    #[serde(rename = "URL", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "URL", default, skip_serializing_if = "String::is_empty")]
     pub url: String,
 
     pub env: Env,
 
     pub schema: SummaryReportSchema,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub steps: Vec<StepReport>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub files: Vec<FileReport>,
 }
 impl SummaryReport {
@@ -179,83 +276,229 @@ impl SummaryReport {
 
         n
     }
+
+    /// Every diagnostic of the report, along with the file and analyzer
+    /// report it belongs to, in file order.
+    pub fn diagnostics(&self) -> impl Iterator<Item = DiagnosticRef<'_>> {
+        self.files.iter().flat_map(|file| {
+            file.reports.iter().flat_map(move |report| {
+                report
+                    .diagnostics
+                    .iter()
+                    .map(move |diagnostic| DiagnosticRef {
+                        file,
+                        report,
+                        diagnostic,
+                    })
+            })
+        })
+    }
+
+    /// The diagnostics reported by the analyzer check `code`, e.g. `DS102`.
+    pub fn diagnostics_with_code<'a>(
+        &'a self,
+        code: &'a str,
+    ) -> impl Iterator<Item = DiagnosticRef<'a>> {
+        self.diagnostics()
+            .filter(move |d| d.diagnostic.code == code)
+    }
+
+    pub fn diagnostics_in_category(
+        &self,
+        category: DiagnosticCategory,
+    ) -> impl Iterator<Item = DiagnosticRef<'_>> {
+        self.diagnostics().filter(move |d| d.category() == category)
+    }
+
+    /// The diagnostics grouped by analyzer code, ordered by code.
+    pub fn diagnostics_by_code(&self) -> BTreeMap<&str, Vec<DiagnosticRef<'_>>> {
+        let mut groups: BTreeMap<&str, Vec<DiagnosticRef>> = BTreeMap::new();
+
+        for d in self.diagnostics() {
+            groups
+                .entry(d.diagnostic.code.as_str())
+                .or_default()
+                .push(d);
+        }
+
+        groups
+    }
+
+    /// Reports whether any diagnostic falls into one of `categories`, e.g. to
+    /// block a merge on destructive or backward incompatible changes only.
+    pub fn has_diagnostics_in(&self, categories: &[DiagnosticCategory]) -> bool {
+        self.diagnostics()
+            .any(|d| categories.contains(&d.category()))
+    }
+}
+
+/// A diagnostic along with the file and analyzer report it was found in.
+#[derive(Debug, Clone, Copy)]
+pub struct DiagnosticRef<'a> {
+    pub file: &'a FileReport,
+    pub report: &'a Report,
+    pub diagnostic: &'a Diagnostic,
+}
+impl DiagnosticRef<'_> {
+    pub fn code(&self) -> &str {
+        &self.diagnostic.code
+    }
+
+    pub fn category(&self) -> DiagnosticCategory {
+        self.diagnostic.category()
+    }
+
+    /// The 1-based line and column the diagnostic points at, if the report
+    /// includes the file text.
+    pub fn line_col(&self) -> Option<(usize, usize)> {
+        line_col(&self.file.text, usize::try_from(self.diagnostic.pos).ok()?)
+    }
+
+    /// The fixes suggested for the diagnostic, or for its report as a whole if
+    /// it has none of its own.
+    pub fn suggested_fixes(&self) -> &[SuggestedFix] {
+        match self.diagnostic.suggested_fixes.is_empty() {
+            true => &self.report.suggested_fixes,
+            false => &self.diagnostic.suggested_fixes,
+        }
+    }
+}
+
+/// The class of change an analyzer check flags, derived from the prefix of
+/// its code. See https://atlasgo.io/lint/analyzers for the full list.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DiagnosticCategory {
+    /// `DS`: dropping schemas, tables or columns.
+    Destructive,
+
+    /// `BC`: renames that break clients of the previous schema.
+    BackwardIncompatible,
+
+    /// `MF`: changes that may fail depending on the data in the database.
+    DataDependent,
+
+    /// `CD`: dropping constraints such as foreign keys.
+    ConstraintDeletion,
+
+    /// `NM`: naming convention violations.
+    Naming,
+
+    /// `DA`: statements such as `UPDATE` or `DELETE` without a `WHERE` clause.
+    DataAccess,
+
+    /// `PG`, `MY`, `LT` and `MS`: driver specific checks such as locking
+    /// index creation on Postgres.
+    DriverSpecific,
+
+    /// Codes with a prefix this crate does not know.
+    Other(String),
+}
+impl DiagnosticCategory {
+    pub fn from_code(code: &str) -> Self {
+        let prefix = code.trim_end_matches(|c: char| c.is_ascii_digit());
+
+        match prefix {
+            "DS" => DiagnosticCategory::Destructive,
+            "BC" => DiagnosticCategory::BackwardIncompatible,
+            "MF" => DiagnosticCategory::DataDependent,
+            "CD" => DiagnosticCategory::ConstraintDeletion,
+            "NM" => DiagnosticCategory::Naming,
+            "DA" => DiagnosticCategory::DataAccess,
+            "PG" | "MY" | "LT" | "MS" => DiagnosticCategory::DriverSpecific,
+            prefix => DiagnosticCategory::Other(prefix.to_string()),
+        }
+    }
+}
+impl std::fmt::Display for DiagnosticCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticCategory::Destructive => write!(f, "destructive"),
+            DiagnosticCategory::BackwardIncompatible => write!(f, "backward-incompatible"),
+            DiagnosticCategory::DataDependent => write!(f, "data-dependent"),
+            DiagnosticCategory::ConstraintDeletion => write!(f, "constraint-deletion"),
+            DiagnosticCategory::Naming => write!(f, "naming"),
+            DiagnosticCategory::DataAccess => write!(f, "data-access"),
+            DiagnosticCategory::DriverSpecific => write!(f, "driver-specific"),
+            DiagnosticCategory::Other(prefix) => write!(f, "{}", prefix),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Env {
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub driver: String,
 
-    #[serde(rename = "URL", skip_serializing_if = "String::is_empty")]
-    pub url: String, // TODO: sqlclient.URL
+    #[serde(rename = "URL", default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<DatabaseUrl>,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub dir: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct SummaryReportSchema {
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub current: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub desired: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct StepReport {
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub name: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub text: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub error: String,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub result: Option<FileReport>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct FileReport {
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub name: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub text: String,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub reports: Vec<Report>,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub error: String,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct StmtError {
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub stmt: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub text: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Changes {
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub applied: Vec<String>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub pending: Vec<String>,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub error: Option<StmtError>,
 }
 
@@ -265,13 +508,381 @@ pub struct SchemaApply {
     #[serde(flatten)]
     pub env: Env,
 
-    #[serde(skip_serializing_if = "changes_all_zero")]
+    #[serde(default, skip_serializing_if = "changes_all_zero")]
     pub changes: Changes,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub error: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SchemaClean {
+    #[serde(flatten)]
+    pub env: Env,
+
+    #[serde(default = "default_time", with = "go_time")]
+    pub start: OffsetDateTime,
+
+    #[serde(default = "default_time", with = "go_time")]
+    pub end: OffsetDateTime,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub applied: Option<AppliedFile>,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub error: String,
+}
+impl SchemaClean {
+    /// The statements that dropped (or, on a dry run, would drop) the
+    /// objects in the cleaned database.
+    pub fn dropped(&self) -> &[String] {
+        match self.applied {
+            Some(ref applied) => &applied.applied,
+            None => &[],
+        }
+    }
+}
+
+/// The difference between a live database and its desired state, as the
+/// statements that would reconcile them.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SchemaDrift {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changes: Vec<DriftChange>,
+}
+impl SchemaDrift {
+    /// Parses the output of `schema diff`: statements, each optionally
+    /// preceded by a `-- ` comment describing it.
+    pub fn from_sql(sql: &str) -> Self {
+        let mut changes = Vec::new();
+        let mut comment = String::new();
+        let mut stmt = String::new();
+
+        for line in sql.lines() {
+            let line = line.trim_end();
+
+            if stmt.is_empty() {
+                if line.trim().is_empty() || line.starts_with("Schemas are synced") {
+                    continue;
+                }
+
+                if let Some(c) = line.strip_prefix("-- ") {
+                    comment = c.to_string();
+                    continue;
+                }
+            } else {
+                stmt.push('\n');
+            }
+
+            stmt.push_str(line);
+
+            if line.ends_with(';') && !in_dollar_quote(&stmt) {
+                changes.push(DriftChange {
+                    comment: std::mem::take(&mut comment),
+                    stmt: std::mem::take(&mut stmt),
+                });
+            }
+        }
+
+        if !stmt.is_empty() {
+            changes.push(DriftChange { comment, stmt });
+        }
+
+        Self { changes }
+    }
+
+    pub fn is_in_sync(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn statements(&self) -> Vec<&str> {
+        self.changes.iter().map(|c| c.stmt.as_str()).collect()
+    }
+
+    /// The reconciling statements as one script.
+    pub fn sql(&self) -> String {
+        self.changes
+            .iter()
+            .map(|c| format!("{}\n", c.stmt))
+            .collect()
+    }
+}
+
+/// Reports whether `sql` ends inside a Postgres dollar-quoted string such
+/// as a `$$ ... $$` function body.
+fn in_dollar_quote(sql: &str) -> bool {
+    let mut open: Option<&str> = None;
+    let mut rest = sql;
+
+    while let Some(start) = rest.find('$') {
+        let after = &rest[start + 1..];
+        let tag_len = after
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+
+        if !after[tag_len..].starts_with('$') {
+            rest = after;
+            continue;
+        }
+
+        let tag = &rest[start..start + tag_len + 2];
+        match open {
+            Some(t) if t == tag => open = None,
+            None => open = Some(tag),
+            _ => {}
+        }
+
+        rest = &rest[start + tag.len()..];
+    }
+
+    open.is_some()
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DriftChange {
+    /// What the statement does, e.g. `Modify "users" table`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub comment: String,
+
+    pub stmt: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SchemaPush {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub link: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub slug: String,
+
+    #[serde(rename = "URL", default, skip_serializing_if = "String::is_empty")]
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SchemaPlan {
+    pub env: Env,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub repo: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lint: Option<SummaryReport>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<SchemaPlanFile>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SchemaPlanFile {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub from_hash: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub to_hash: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub migration: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stmts: Vec<Stmt>,
+
+    #[serde(rename = "URL", default, skip_serializing_if = "String::is_empty")]
+    pub url: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub link: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub status: String,
+}
+
+/// A plan pulled from the registry by `schema plan pull`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SchemaPlanPull {
+    /// The registry url the plan was pulled from.
+    #[serde(rename = "URL")]
+    pub url: String,
+
+    /// The plan file as atlas prints it, with the migration inline.
+    pub plan: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SchemaPlanApprove {
+    #[serde(rename = "URL", default, skip_serializing_if = "String::is_empty")]
+    pub url: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub link: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub status: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Stmt {
+    pub pos: isize,
+
+    pub text: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WhoAmI {
+    pub org: WhoAmIOrg,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<WhoAmIUser>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WhoAmIOrg {
+    pub id: isize,
+
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WhoAmIUser {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub email: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Realm {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schemas: Vec<Schema>,
+}
+impl Realm {
+    pub fn schema(&self, name: &str) -> Option<&Schema> {
+        self.schemas.iter().find(|s| s.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Schema {
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tables: Vec<Table>,
+}
+impl Schema {
+    pub fn table(&self, name: &str) -> Option<&Table> {
+        self.tables.iter().find(|t| t.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Table {
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<Column>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub indexes: Vec<Index>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub primary_key: Option<Index>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub foreign_keys: Vec<ForeignKey>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<Check>,
+}
+impl Table {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|c| c.name == name)
+    }
+
+    pub fn index(&self, name: &str) -> Option<&Index> {
+        self.indexes.iter().find(|i| i.name == name)
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Column {
+    pub name: String,
+
+    #[serde(rename = "type", default, skip_serializing_if = "String::is_empty")]
+    pub typ: String,
+
+    #[serde(default, skip_serializing_if = "bool_is_zero")]
+    pub null: bool,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub comment: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Index {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "bool_is_zero")]
+    pub unique: bool,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parts: Vec<IndexPart>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct IndexPart {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub column: String,
+
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub expr: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ForeignKey {
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<String>,
+
+    pub references: ForeignKeyReferences,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ForeignKeyReferences {
+    pub table: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub columns: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Check {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+
+    pub expr: String,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct Revision {
@@ -286,15 +897,16 @@ pub struct Revision {
 
     pub total: isize,
 
-    #[serde(default = "default_time")]
-    pub executed_at: PrimitiveDateTime,
+    #[serde(default = "default_time", with = "go_time")]
+    pub executed_at: OffsetDateTime,
 
+    #[serde(with = "go_duration")]
     pub execution_time: Duration,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub error: String,
 
-    #[serde(skip_serializing_if = "String::is_empty")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub error_stmt: String,
 
     pub operator_version: String,
@@ -305,10 +917,10 @@ pub struct Revision {
 pub struct Version {
     pub version: String,
 
-    #[serde(rename = "SHA", skip_serializing_if = "String::is_empty")]
+    #[serde(rename = "SHA", default, skip_serializing_if = "String::is_empty")]
     pub sha: String,
 
-    #[serde(skip_serializing_if = "bool_is_zero")]
+    #[serde(default, skip_serializing_if = "bool_is_zero")]
     pub canary: bool,
 }
 
@@ -353,10 +965,10 @@ impl SchemaApplyError {
 pub struct Report {
     pub text: String,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub diagnostics: Vec<Diagnostic>,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub suggested_fixes: Vec<SuggestedFix>,
 }
 
@@ -369,16 +981,21 @@ pub struct Diagnostic {
 
     pub code: String,
 
-    #[serde(skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub suggested_fixes: Vec<SuggestedFix>,
 }
+impl Diagnostic {
+    pub fn category(&self) -> DiagnosticCategory {
+        DiagnosticCategory::from_code(&self.code)
+    }
+}
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "PascalCase")]
 pub struct SuggestedFix {
     pub message: String,
 
-    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub text_edit: Option<TextEdit>,
 }
 
@@ -392,18 +1009,6 @@ pub struct TextEdit {
     pub new_text: String,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-#[serde(rename_all = "PascalCase")]
-pub struct URL {
-    #[serde(flatten)]
-    pub url: Option<url::Url>,
-
-    #[serde(skip)]
-    pub dsn: String,
-
-    pub schema: String,
-}
-
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct SqlError {
     #[serde(rename = "SQL")]
@@ -413,8 +1018,72 @@ pub struct SqlError {
     pub error: String,
 }
 
-fn default_time() -> PrimitiveDateTime {
-    PrimitiveDateTime::new(date!(0001 - 01 - 01), time!(0:00))
+/// Go's zero `time.Time`, which atlas emits for timestamps it never set.
+pub const GO_ZERO_TIME: OffsetDateTime = datetime!(0001-01-01 0:00 UTC);
+
+/// Reports whether `t` is Go's zero time, i.e. the timestamp was never set.
+pub fn is_zero_time(t: &OffsetDateTime) -> bool {
+    *t == GO_ZERO_TIME
+}
+
+fn default_time() -> OffsetDateTime {
+    GO_ZERO_TIME
+}
+
+/// (De)serializes timestamps in the RFC 3339 form Go's `time.Time` uses,
+/// keeping the offset and up to nanosecond precision.
+mod go_time {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+    pub fn serialize<S>(t: &OffsetDateTime, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let s = t.format(&Rfc3339).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_str(&s)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<OffsetDateTime, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        OffsetDateTime::parse(&s, &Rfc3339).map_err(serde::de::Error::custom)
+    }
+}
+
+/// (De)serializes durations as the integer nanoseconds Go's `time.Duration`
+/// marshals to.
+mod go_duration {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use time::Duration;
+
+    pub fn serialize<S>(d: &Duration, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let nanos = i64::try_from(d.whole_nanoseconds()).map_err(serde::ser::Error::custom)?;
+        serializer.serialize_i64(nanos)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(Duration::nanoseconds(i64::deserialize(deserializer)?))
+    }
+}
+
+/// Deserializes a status, treating the empty status atlas reports when it
+/// has none as missing.
+fn empty_status_as_none<'de, D>(deserializer: D) -> Result<Option<MigrationStatus>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let status = Option::<String>::deserialize(deserializer)?;
+
+    Ok(status.filter(|s| !s.is_empty()).map(MigrationStatus::from))
 }
 
 fn isize_is_zero(val: &isize) -> bool {
@@ -428,3 +1097,467 @@ fn changes_all_zero(changes: &Changes) -> bool {
 fn bool_is_zero(b: &bool) -> bool {
     !b
 }
+
+#[cfg(test)]
+mod tests {
+    use time::macros::offset;
+
+    use super::*;
+
+    // migrate apply --format '{{ json . }}' against postgres, on a host in
+    // UTC+2
+    const MIGRATE_APPLY: &str = r#"{
+        "Driver": "postgres",
+        "Dir": "file://migrations",
+        "Pending": [{"Name": "20240510120000_create_users.sql", "Version": "20240510120000", "Description": "create_users"}],
+        "Applied": [{
+            "Name": "20240510120000_create_users.sql",
+            "Version": "20240510120000",
+            "Description": "create_users",
+            "Start": "2024-05-10T14:03:21.123456789+02:00",
+            "End": "2024-05-10T14:03:21.130001+02:00",
+            "Skipped": 0,
+            "Applied": ["CREATE TABLE \"users\" (\"id\" bigint NOT NULL);"],
+            "Error": null
+        }],
+        "Target": "20240510120000",
+        "Start": "2024-05-10T14:03:21.120000001+02:00",
+        "End": "2024-05-10T12:03:21.131Z"
+    }"#;
+
+    // migrate status --format '{{ json . }}' after the apply above
+    const MIGRATE_STATUS: &str = r#"{
+        "Available": [{"Name": "20240510120000_create_users.sql", "Version": "20240510120000", "Description": "create_users"}],
+        "Applied": [{
+            "Version": "20240510120000",
+            "Description": "create_users",
+            "Type": "applied",
+            "Applied": 1,
+            "Total": 1,
+            "ExecutedAt": "2024-05-10T14:03:21.123456789+02:00",
+            "ExecutionTime": 6544211,
+            "Hash": "nXyZ5hEPbS0EQkKa/yr0Xz0cGeXWKvKOuVwNfVGKy2s=",
+            "OperatorVersion": "Atlas CLI v0.24.0"
+        }],
+        "Current": "20240510120000",
+        "Total": 1,
+        "Status": "OK"
+    }"#;
+
+    #[test]
+    fn go_time_keeps_offset_and_nanoseconds() {
+        let apply: MigrateApply = serde_json::from_str(MIGRATE_APPLY).unwrap();
+
+        let file = &apply.applied[0];
+        assert_eq!(file.start.offset(), offset!(+2));
+        assert_eq!(file.start.nanosecond(), 123_456_789);
+        assert_eq!(file.end.nanosecond(), 130_001_000);
+        assert_eq!(apply.start.nanosecond(), 120_000_001);
+        assert_eq!(apply.end.offset(), offset!(UTC));
+        assert_eq!(apply.end - apply.start, Duration::nanoseconds(10_999_999));
+    }
+
+    #[test]
+    fn go_time_zero_value() {
+        let apply: MigrateApply = serde_json::from_str(
+            r#"{"Start": "0001-01-01T00:00:00Z", "End": "0001-01-01T00:00:00Z"}"#,
+        )
+        .unwrap();
+        assert!(is_zero_time(&apply.start));
+        assert!(is_zero_time(&apply.end));
+
+        let missing: MigrateApply = serde_json::from_str("{}").unwrap();
+        assert!(is_zero_time(&missing.start));
+
+        let json = serde_json::to_value(&missing).unwrap();
+        assert_eq!(json["Start"], "0001-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn go_duration_is_integer_nanoseconds() {
+        let status: MigrateStatus = serde_json::from_str(MIGRATE_STATUS).unwrap();
+
+        let revision = &status.applied[0];
+        assert_eq!(revision.execution_time, Duration::nanoseconds(6_544_211));
+        assert_eq!(revision.executed_at.nanosecond(), 123_456_789);
+
+        let json = serde_json::to_value(revision).unwrap();
+        assert_eq!(json["ExecutionTime"], 6_544_211);
+    }
+
+    #[test]
+    fn migration_status_round_trip() {
+        let statuses = [
+            ("OK", MigrationStatus::Ok),
+            ("PENDING", MigrationStatus::Pending),
+            ("PENDING_USER", MigrationStatus::PendingUser),
+            ("APPROVED", MigrationStatus::Approved),
+            ("ABORTED", MigrationStatus::Aborted),
+            ("FAILED", MigrationStatus::Failed),
+            (
+                "PARTIALLY_APPLIED",
+                MigrationStatus::Unknown("PARTIALLY_APPLIED".into()),
+            ),
+        ];
+
+        for (raw, status) in statuses {
+            let json = format!(r#"{{"Status": "{}"}}"#, raw);
+            let parsed: MigrateStatus = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed.status, Some(status.clone()));
+
+            let value = serde_json::to_value(&parsed).unwrap();
+            assert_eq!(value["Status"], raw);
+
+            let again: MigrateDown = serde_json::from_value(value).unwrap();
+            assert_eq!(again.status, Some(status));
+        }
+
+        for json in [r#"{"Status": ""}"#, r#"{"Status": null}"#, "{}"] {
+            let status: MigrateStatus = serde_json::from_str(json).unwrap();
+            assert_eq!(status.status, None);
+
+            let down: MigrateDown = serde_json::from_str(json).unwrap();
+            assert_eq!(down.status, None);
+            assert!(serde_json::to_value(&down).unwrap().get("Status").is_none());
+        }
+    }
+
+    #[test]
+    fn go_time_round_trip() {
+        let apply: MigrateApply = serde_json::from_str(MIGRATE_APPLY).unwrap();
+
+        let json = serde_json::to_string(&apply).unwrap();
+        assert!(json.contains(r#""Start":"2024-05-10T14:03:21.123456789+02:00""#));
+
+        let again: MigrateApply = serde_json::from_str(&json).unwrap();
+        assert_eq!(again.start, apply.start);
+        assert_eq!(again.start.offset(), apply.start.offset());
+        assert_eq!(again.end, apply.end);
+        assert_eq!(again.applied[0].start, apply.applied[0].start);
+        assert_eq!(again.applied[0].end, apply.applied[0].end);
+
+        let status: MigrateStatus = serde_json::from_str(MIGRATE_STATUS).unwrap();
+        let json = serde_json::to_string(&status).unwrap();
+        let again: MigrateStatus = serde_json::from_str(&json).unwrap();
+        assert_eq!(again.applied[0].executed_at, status.applied[0].executed_at);
+        assert_eq!(
+            again.applied[0].execution_time,
+            status.applied[0].execution_time
+        );
+    }
+
+    #[test]
+    fn schema_plan_push_file() {
+        // schema plan push --format '{{ json . }}'
+        let json = r#"[{
+            "Env": {"Driver": "postgres", "URL": {"Scheme": "postgres", "Host": "localhost:5432"}},
+            "Repo": "app",
+            "File": {
+                "Name": "20240510120000",
+                "FromHash": "oVb8oGRvW0iNHv0VRWGbXDLfFJrvr4QwjIa0O6ds9ZA=",
+                "ToHash": "pSm2u6/mEQOwbhjY7mObA3ljwY1u3ePVyY5IGb3Ml/o=",
+                "Migration": "-- Add column \"email\" to table: \"users\"\nALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;\n",
+                "Stmts": [{"Pos": 0, "Text": "ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;", "Comments": ["-- Add column \"email\" to table: \"users\"\n"]}],
+                "URL": "atlas://app/plans/20240510120000",
+                "Link": "https://acme.atlasgo.cloud/schemas/1/plans/2",
+                "Status": "PENDING"
+            }
+        }]"#;
+
+        let plans: Vec<SchemaPlan> = serde_json::from_str(json).unwrap();
+        let file = plans[0].file.as_ref().unwrap();
+        assert_eq!(file.url, "atlas://app/plans/20240510120000");
+        assert_eq!(file.status, "PENDING");
+        assert_eq!(file.stmts.len(), 1);
+    }
+
+    #[test]
+    fn summary_report_diagnostics() {
+        let report: SummaryReport =
+            serde_json::from_str(include_str!("../testdata/lint.json")).unwrap();
+
+        assert_eq!(report.diagnostics_count(), 3);
+        assert_eq!(
+            report
+                .diagnostics()
+                .map(|d| d.diagnostic.code.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["DS103", "MF103", "PG101"]
+        );
+
+        let ds103 = report.diagnostics_with_code("DS103").collect::<Vec<_>>();
+        assert_eq!(ds103.len(), 1);
+        assert_eq!(ds103[0].file.name, "20240510130000_drop_email.sql");
+        assert_eq!(ds103[0].report.text, "destructive changes detected");
+        // the position is a byte offset past the multibyte comment
+        assert_eq!(ds103[0].line_col(), Some((2, 1)));
+        // no fixes of its own, so it inherits those of its report
+        assert_eq!(
+            ds103[0].suggested_fixes()[0]
+                .text_edit
+                .as_ref()
+                .unwrap()
+                .line,
+            2
+        );
+
+        let pg101 = report.diagnostics_with_code("PG101").next().unwrap();
+        assert_eq!(pg101.line_col(), Some((2, 1)));
+        assert_eq!(
+            pg101.suggested_fixes()[0].message,
+            "Add the CONCURRENTLY keyword to the SQL command"
+        );
+        assert!(pg101.suggested_fixes()[0].text_edit.is_none());
+
+        assert_eq!(report.diagnostics_with_code("DS102").count(), 0);
+
+        let codes = |category| {
+            report
+                .diagnostics_in_category(category)
+                .map(|d| d.diagnostic.code.as_str())
+                .collect::<Vec<&str>>()
+        };
+        assert_eq!(codes(DiagnosticCategory::Destructive), vec!["DS103"]);
+        assert_eq!(codes(DiagnosticCategory::DataDependent), vec!["MF103"]);
+        assert_eq!(codes(DiagnosticCategory::DriverSpecific), vec!["PG101"]);
+        assert!(codes(DiagnosticCategory::Naming).is_empty());
+
+        let by_code = report.diagnostics_by_code();
+        assert_eq!(
+            by_code.keys().copied().collect::<Vec<&str>>(),
+            vec!["DS103", "MF103", "PG101"]
+        );
+        assert_eq!(by_code["MF103"][0].file.name, "20240510140000_add_age.sql");
+
+        assert!(report.has_diagnostics_in(&[
+            DiagnosticCategory::BackwardIncompatible,
+            DiagnosticCategory::Destructive
+        ]));
+        assert!(!report.has_diagnostics_in(&[
+            DiagnosticCategory::BackwardIncompatible,
+            DiagnosticCategory::ConstraintDeletion
+        ]));
+        assert!(!report.has_diagnostics_in(&[]));
+    }
+
+    #[test]
+    fn diagnostic_category_from_code() {
+        let tests = [
+            ("DS102", DiagnosticCategory::Destructive),
+            ("BC101", DiagnosticCategory::BackwardIncompatible),
+            ("MF103", DiagnosticCategory::DataDependent),
+            ("CD101", DiagnosticCategory::ConstraintDeletion),
+            ("NM102", DiagnosticCategory::Naming),
+            ("DA101", DiagnosticCategory::DataAccess),
+            ("PG101", DiagnosticCategory::DriverSpecific),
+            ("MY101", DiagnosticCategory::DriverSpecific),
+            ("LT101", DiagnosticCategory::DriverSpecific),
+            ("MS101", DiagnosticCategory::DriverSpecific),
+            ("AR101", DiagnosticCategory::Other("AR".into())),
+            ("", DiagnosticCategory::Other("".into())),
+        ];
+
+        for (code, want) in tests {
+            assert_eq!(DiagnosticCategory::from_code(code), want, "{}", code);
+        }
+
+        assert_eq!(DiagnosticCategory::Destructive.to_string(), "destructive");
+        assert_eq!(
+            DiagnosticCategory::BackwardIncompatible.to_string(),
+            "backward-incompatible"
+        );
+        assert_eq!(DiagnosticCategory::Other("AR".into()).to_string(), "AR");
+    }
+
+    fn drift(sql: &str) -> Vec<(String, String)> {
+        SchemaDrift::from_sql(sql)
+            .changes
+            .into_iter()
+            .map(|c| (c.comment, c.stmt))
+            .collect()
+    }
+
+    fn changes(c: &[(&str, &str)]) -> Vec<(String, String)> {
+        c.iter()
+            .map(|(c, s)| (c.to_string(), s.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn schema_drift_from_sql() {
+        let tests = [
+            ("", vec![]),
+            ("\n\n", vec![]),
+            ("Schemas are synced, no changes to be made.\n", vec![]),
+            (
+                // schema diff against postgres
+                "-- Modify \"users\" table\nALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;\n-- Create index \"users_email\" to table: \"users\"\nCREATE INDEX \"users_email\" ON \"users\" (\"email\");\n",
+                vec![
+                    (
+                        "Modify \"users\" table",
+                        "ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;",
+                    ),
+                    (
+                        "Create index \"users_email\" to table: \"users\"",
+                        "CREATE INDEX \"users_email\" ON \"users\" (\"email\");",
+                    ),
+                ],
+            ),
+            (
+                // statements without comments, separated by blank lines
+                "DROP TABLE \"a\";\n\nDROP TABLE \"b\";   \n",
+                vec![("", "DROP TABLE \"a\";"), ("", "DROP TABLE \"b\";")],
+            ),
+            (
+                // multi-line statements keep their indentation
+                "-- Create \"posts\" table\nCREATE TABLE \"posts\" (\n  \"id\" bigint NOT NULL,\n  \"body\" text NULL,\n  PRIMARY KEY (\"id\")\n);\n",
+                vec![(
+                    "Create \"posts\" table",
+                    "CREATE TABLE \"posts\" (\n  \"id\" bigint NOT NULL,\n  \"body\" text NULL,\n  PRIMARY KEY (\"id\")\n);",
+                )],
+            ),
+            (
+                // a comment inside a statement belongs to it
+                "ALTER TABLE \"users\"\n-- keep the data\n  DROP COLUMN \"email\";\n",
+                vec![(
+                    "",
+                    "ALTER TABLE \"users\"\n-- keep the data\n  DROP COLUMN \"email\";",
+                )],
+            ),
+            (
+                // semicolons inside $$ bodies do not end the statement
+                "-- Create \"touch\" function\nCREATE FUNCTION \"touch\" () RETURNS trigger LANGUAGE plpgsql AS $$\nBEGIN\n  NEW.updated_at = now();\n  RETURN NEW;\nEND;\n$$;\n-- Create trigger \"users_touch\"\nCREATE TRIGGER \"users_touch\" BEFORE UPDATE ON \"users\" FOR EACH ROW EXECUTE FUNCTION \"touch\"();\n",
+                vec![
+                    (
+                        "Create \"touch\" function",
+                        "CREATE FUNCTION \"touch\" () RETURNS trigger LANGUAGE plpgsql AS $$\nBEGIN\n  NEW.updated_at = now();\n  RETURN NEW;\nEND;\n$$;",
+                    ),
+                    (
+                        "Create trigger \"users_touch\"",
+                        "CREATE TRIGGER \"users_touch\" BEFORE UPDATE ON \"users\" FOR EACH ROW EXECUTE FUNCTION \"touch\"();",
+                    ),
+                ],
+            ),
+            (
+                // tagged bodies may contain other dollar quotes and positional
+                // parameters
+                "CREATE FUNCTION \"f\" (int) RETURNS text AS $body$\nSELECT $$a;b$$ || $1::text;\n$body$ LANGUAGE sql;\nDROP TABLE \"t\";\n",
+                vec![
+                    (
+                        "",
+                        "CREATE FUNCTION \"f\" (int) RETURNS text AS $body$\nSELECT $$a;b$$ || $1::text;\n$body$ LANGUAGE sql;",
+                    ),
+                    ("", "DROP TABLE \"t\";"),
+                ],
+            ),
+            (
+                // a trailing statement without a semicolon is kept
+                "-- Drop \"legacy\" table\nDROP TABLE \"legacy\"",
+                vec![("Drop \"legacy\" table", "DROP TABLE \"legacy\"")],
+            ),
+        ];
+
+        for (sql, want) in tests {
+            assert_eq!(drift(sql), changes(&want), "{}", sql);
+        }
+
+        let synced = SchemaDrift::from_sql("Schemas are synced, no changes to be made.\n");
+        assert!(synced.is_in_sync());
+        assert_eq!(synced.sql(), "");
+
+        let d = SchemaDrift::from_sql("-- a\nDROP TABLE \"a\";\nDROP TABLE \"b\";\n");
+        assert!(!d.is_in_sync());
+        assert_eq!(
+            d.statements(),
+            vec!["DROP TABLE \"a\";", "DROP TABLE \"b\";"]
+        );
+        assert_eq!(d.sql(), "DROP TABLE \"a\";\nDROP TABLE \"b\";\n");
+    }
+
+    #[test]
+    fn dollar_quotes() {
+        let tests = [
+            ("SELECT 1;", false),
+            ("AS $$ BEGIN", true),
+            ("AS $$ BEGIN END; $$;", false),
+            ("AS $fn$ SELECT $$x$$;", true),
+            ("AS $fn$ SELECT $$x$$; $fn$;", false),
+            ("AS $fn$ SELECT 1; $other$", true),
+            ("SELECT $1, $2;", false),
+            ("SELECT price$ FROM t;", false),
+            ("SELECT $_tag_1$ ; ", true),
+            ("$", false),
+        ];
+
+        for (sql, want) in tests {
+            assert_eq!(in_dollar_quote(sql), want, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn realm_from_schema_inspect() {
+        // schema inspect --format '{{ json . }}' against postgres
+        let realm: Realm =
+            serde_json::from_str(include_str!("../testdata/schema_inspect.json")).unwrap();
+
+        assert_eq!(
+            realm
+                .schemas
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["audit", "public"]
+        );
+        assert!(realm.schema("audit").unwrap().tables.is_empty());
+        assert!(realm.schema("private").is_none());
+
+        let public = realm.schema("public").unwrap();
+        assert_eq!(public.tables.len(), 2);
+        assert!(public.table("active_users").is_none());
+
+        let users = public.table("users").unwrap();
+        let email = users.column("email").unwrap();
+        assert_eq!(email.typ, "character varying(255)");
+        assert!(email.null);
+        assert!(!users.column("age").unwrap().null);
+        assert!(users.column("name").is_none());
+        assert!(users.foreign_keys.is_empty());
+        assert!(users.checks.is_empty());
+
+        let pk = users.primary_key.as_ref().unwrap();
+        assert_eq!(pk.name, "");
+        assert_eq!(pk.parts[0].column, "id");
+
+        let posts = public.table("posts").unwrap();
+        let unique = posts.index("posts_author_title").unwrap();
+        assert!(unique.unique);
+        assert_eq!(
+            unique
+                .parts
+                .iter()
+                .map(|p| p.column.as_str())
+                .collect::<Vec<&str>>(),
+            vec!["author_id", "title"]
+        );
+
+        let lower = posts.index("posts_lower_title").unwrap();
+        assert!(!lower.unique);
+        assert_eq!(lower.parts[0].column, "");
+        assert_eq!(lower.parts[0].expr, "lower((title)::text)");
+
+        let fk = &posts.foreign_keys[0];
+        assert_eq!(fk.name, "posts_author_fk");
+        assert_eq!(fk.columns, vec!["author_id"]);
+        assert_eq!(fk.references.table, "users");
+        assert_eq!(fk.references.columns, vec!["id"]);
+
+        // serializes back to the same shape, minus what the models skip
+        let json = serde_json::to_value(&realm).unwrap();
+        assert_eq!(json["schemas"][0], serde_json::json!({"name": "audit"}));
+        assert_eq!(
+            json["schemas"][1]["tables"][1]["columns"][1],
+            serde_json::json!({"name": "email", "type": "character varying(255)", "null": true})
+        );
+        assert!(json["schemas"][1].get("views").is_none());
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::process::Command;
+
+use crate::atlas::{DeployRunContext, RunContext, TriggerType};
+
+/// Metadata about the git checkout the process runs in, used to fill in
+/// whatever the CI environment does not provide.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GitInfo {
+    /// The url of the `origin` remote.
+    pub remote_url: String,
+    pub branch: String,
+    pub commit: String,
+    pub author: String,
+}
+impl GitInfo {
+    /// Reads the git metadata of the repository containing `dir`, or the
+    /// current directory if `dir` is `None`. Anything git cannot tell (or
+    /// a missing git binary) leaves the field empty.
+    pub fn from_repo(dir: Option<&Path>) -> Self {
+        let git = |args: &[&str]| -> String {
+            let mut cmd = Command::new("git");
+            cmd.args(args);
+
+            if let Some(dir) = dir {
+                cmd.current_dir(dir);
+            }
+
+            match cmd.output() {
+                Ok(output) if output.status.success() => String::from_utf8(output.stdout)
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string(),
+                _ => String::new(),
+            }
+        };
+
+        let branch = git(&["rev-parse", "--abbrev-ref", "HEAD"]);
+
+        Self {
+            remote_url: git(&["config", "--get", "remote.origin.url"]),
+            // a detached HEAD has no branch
+            branch: if branch == "HEAD" {
+                String::new()
+            } else {
+                branch
+            },
+            commit: git(&["rev-parse", "HEAD"]),
+            author: git(&["log", "-1", "--format=%an"]),
+        }
+    }
+}
+
+/// The CI systems whose environment variables are understood.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiProvider {
+    GithubActions,
+    GitlabCi,
+    CircleCi,
+    Buildkite,
+    Jenkins,
+}
+impl CiProvider {
+    pub fn detect_from(env: &HashMap<String, String>) -> Option<Self> {
+        let is_set = |key: &str| env.get(key).is_some_and(|v| !v.is_empty());
+
+        if is_set("GITHUB_ACTIONS") {
+            Some(CiProvider::GithubActions)
+        } else if is_set("GITLAB_CI") {
+            Some(CiProvider::GitlabCi)
+        } else if is_set("CIRCLECI") {
+            Some(CiProvider::CircleCi)
+        } else if is_set("BUILDKITE") {
+            Some(CiProvider::Buildkite)
+        } else if is_set("JENKINS_URL") {
+            Some(CiProvider::Jenkins)
+        } else {
+            None
+        }
+    }
+}
+
+impl RunContext {
+    /// Builds a run context from the CI environment of the current process,
+    /// falling back to the local git checkout.
+    pub fn detect() -> Self {
+        Self::detect_from(&std::env::vars().collect(), &GitInfo::from_repo(None))
+    }
+
+    /// Builds a run context from the given environment variables, filling in
+    /// anything they do not provide from `git`.
+    pub fn detect_from(env: &HashMap<String, String>, git: &GitInfo) -> Self {
+        let var = |key: &str| env.get(key).cloned().unwrap_or_default();
+        let first = |keys: &[&str]| {
+            keys.iter()
+                .map(|k| var(k))
+                .find(|v| !v.is_empty())
+                .unwrap_or_default()
+        };
+
+        let mut ctx = match CiProvider::detect_from(env) {
+            Some(CiProvider::GithubActions) => RunContext {
+                repo: var("GITHUB_REPOSITORY"),
+                branch: first(&["GITHUB_HEAD_REF", "GITHUB_REF_NAME"]),
+                commit: var("GITHUB_SHA"),
+                url: match (
+                    var("GITHUB_SERVER_URL"),
+                    var("GITHUB_REPOSITORY"),
+                    var("GITHUB_RUN_ID"),
+                ) {
+                    (server, repo, run) if !server.is_empty() && !run.is_empty() => {
+                        format!("{}/{}/actions/runs/{}", server, repo, run)
+                    }
+                    _ => String::new(),
+                },
+                username: var("GITHUB_ACTOR"),
+                user_id: var("GITHUB_ACTOR_ID"),
+                scm_type: "GITHUB".into(),
+                ..RunContext::default()
+            },
+            Some(CiProvider::GitlabCi) => RunContext {
+                repo: var("CI_PROJECT_PATH"),
+                branch: first(&["CI_MERGE_REQUEST_SOURCE_BRANCH_NAME", "CI_COMMIT_REF_NAME"]),
+                commit: var("CI_COMMIT_SHA"),
+                url: first(&["CI_MERGE_REQUEST_PROJECT_URL", "CI_PIPELINE_URL"]),
+                username: var("GITLAB_USER_LOGIN"),
+                user_id: var("GITLAB_USER_ID"),
+                scm_type: "GITLAB".into(),
+                ..RunContext::default()
+            },
+            Some(CiProvider::CircleCi) => RunContext {
+                repo: match (
+                    var("CIRCLE_PROJECT_USERNAME"),
+                    var("CIRCLE_PROJECT_REPONAME"),
+                ) {
+                    (owner, name) if !owner.is_empty() && !name.is_empty() => {
+                        format!("{}/{}", owner, name)
+                    }
+                    _ => repo_slug(&var("CIRCLE_REPOSITORY_URL")),
+                },
+                branch: var("CIRCLE_BRANCH"),
+                commit: var("CIRCLE_SHA1"),
+                url: first(&["CIRCLE_PULL_REQUEST", "CIRCLE_BUILD_URL"]),
+                username: var("CIRCLE_USERNAME"),
+                scm_type: scm_type(&var("CIRCLE_REPOSITORY_URL")),
+                ..RunContext::default()
+            },
+            Some(CiProvider::Buildkite) => RunContext {
+                repo: repo_slug(&var("BUILDKITE_REPO")),
+                branch: var("BUILDKITE_BRANCH"),
+                commit: var("BUILDKITE_COMMIT"),
+                url: var("BUILDKITE_BUILD_URL"),
+                username: var("BUILDKITE_BUILD_CREATOR"),
+                scm_type: scm_type(&var("BUILDKITE_REPO")),
+                ..RunContext::default()
+            },
+            Some(CiProvider::Jenkins) => RunContext {
+                repo: repo_slug(&var("GIT_URL")),
+                branch: {
+                    let branch = first(&["CHANGE_BRANCH", "BRANCH_NAME", "GIT_BRANCH"]);
+                    branch
+                        .strip_prefix("origin/")
+                        .map(String::from)
+                        .unwrap_or(branch)
+                },
+                commit: var("GIT_COMMIT"),
+                url: first(&["CHANGE_URL", "BUILD_URL"]),
+                username: first(&["CHANGE_AUTHOR", "GIT_AUTHOR_NAME"]),
+                scm_type: scm_type(&var("GIT_URL")),
+                ..RunContext::default()
+            },
+            None => RunContext::default(),
+        };
+
+        if ctx.repo.is_empty() {
+            ctx.repo = repo_slug(&git.remote_url);
+        }
+
+        if ctx.branch.is_empty() {
+            ctx.branch = git.branch.clone();
+        }
+
+        if ctx.commit.is_empty() {
+            ctx.commit = git.commit.clone();
+        }
+
+        if ctx.username.is_empty() {
+            ctx.username = git.author.clone();
+        }
+
+        if ctx.scm_type.is_empty() {
+            ctx.scm_type = scm_type(&git.remote_url);
+        }
+
+        ctx
+    }
+}
+
+impl DeployRunContext {
+    /// Builds a deploy context for the CI system the current process runs in.
+    /// `trigger_version` is the version of the tool that triggered the
+    /// deployment, e.g. the CI action or the deploy tool built on this crate.
+    pub fn detect(trigger_version: &str) -> Self {
+        Self::detect_from(&std::env::vars().collect(), trigger_version)
+    }
+
+    /// Builds a deploy context from the given environment variables. Runs
+    /// outside a CI system Atlas Cloud has a trigger type for are reported
+    /// as CLI runs.
+    pub fn detect_from(env: &HashMap<String, String>, trigger_version: &str) -> Self {
+        let trigger_type = match CiProvider::detect_from(env) {
+            Some(CiProvider::GithubActions) => TriggerType::GithubAction,
+            Some(CiProvider::CircleCi) => TriggerType::CircleCiOrb,
+            _ => TriggerType::Cli,
+        };
+
+        Self {
+            trigger_type,
+            trigger_version: trigger_version.into(),
+        }
+    }
+}
+
+/// Turns a git remote url such as `git@github.com:org/repo.git` or
+/// `https://gitlab.com/group/sub/repo` into `org/repo` / `group/sub/repo`.
+fn repo_slug(remote_url: &str) -> String {
+    let url = remote_url.trim().trim_end_matches('/');
+    let url = url.strip_suffix(".git").unwrap_or(url);
+
+    let path = match url.split_once("://") {
+        // https://host/org/repo or ssh://git@host:22/org/repo
+        Some((_, rest)) => rest.split_once('/').map(|(_, p)| p).unwrap_or_default(),
+        // scp-like git@host:org/repo
+        None => url.split_once(':').map(|(_, p)| p).unwrap_or(url),
+    };
+
+    path.trim_matches('/').to_string()
+}
+
+fn scm_type(remote_url: &str) -> String {
+    let url = remote_url.to_lowercase();
+
+    if url.contains("github") {
+        "GITHUB".into()
+    } else if url.contains("gitlab") {
+        "GITLAB".into()
+    } else if url.contains("bitbucket") {
+        "BITBUCKET".into()
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    fn git() -> GitInfo {
+        GitInfo {
+            remote_url: "git@github.com:local/checkout.git".into(),
+            branch: "local-branch".into(),
+            commit: "0000000".into(),
+            author: "Local Author".into(),
+        }
+    }
+
+    #[test]
+    fn detect_github_actions() {
+        let env = env(&[
+            ("GITHUB_ACTIONS", "true"),
+            ("GITHUB_REPOSITORY", "ariga/app"),
+            ("GITHUB_HEAD_REF", ""),
+            ("GITHUB_REF_NAME", "main"),
+            ("GITHUB_SHA", "4f1c2b7"),
+            ("GITHUB_SERVER_URL", "https://github.com"),
+            ("GITHUB_RUN_ID", "9182736450"),
+            ("GITHUB_ACTOR", "octocat"),
+            ("GITHUB_ACTOR_ID", "583231"),
+        ]);
+
+        let ctx = RunContext::detect_from(&env, &git());
+        assert_eq!(ctx.repo, "ariga/app");
+        assert_eq!(ctx.branch, "main");
+        assert_eq!(ctx.commit, "4f1c2b7");
+        assert_eq!(
+            ctx.url,
+            "https://github.com/ariga/app/actions/runs/9182736450"
+        );
+        assert_eq!(ctx.username, "octocat");
+        assert_eq!(ctx.user_id, "583231");
+        assert_eq!(ctx.scm_type, "GITHUB");
+
+        let deploy = DeployRunContext::detect_from(&env, "v1.2.0");
+        assert_eq!(deploy.trigger_type, TriggerType::GithubAction);
+        assert_eq!(deploy.trigger_version, "v1.2.0");
+    }
+
+    #[test]
+    fn detect_gitlab_merge_request() {
+        let env = env(&[
+            ("GITLAB_CI", "true"),
+            ("CI_PROJECT_PATH", "group/sub/app"),
+            ("CI_MERGE_REQUEST_SOURCE_BRANCH_NAME", "feature/users"),
+            ("CI_COMMIT_REF_NAME", "refs/merge-requests/7/head"),
+            ("CI_COMMIT_SHA", "a1b2c3d"),
+            (
+                "CI_PIPELINE_URL",
+                "https://gitlab.com/group/sub/app/-/pipelines/42",
+            ),
+            ("GITLAB_USER_LOGIN", "tanuki"),
+            ("GITLAB_USER_ID", "17"),
+        ]);
+
+        let ctx = RunContext::detect_from(&env, &git());
+        assert_eq!(ctx.repo, "group/sub/app");
+        assert_eq!(ctx.branch, "feature/users");
+        assert_eq!(ctx.commit, "a1b2c3d");
+        assert_eq!(ctx.url, "https://gitlab.com/group/sub/app/-/pipelines/42");
+        assert_eq!(ctx.username, "tanuki");
+        assert_eq!(ctx.user_id, "17");
+        assert_eq!(ctx.scm_type, "GITLAB");
+
+        // Atlas Cloud has no GitLab trigger type
+        let deploy = DeployRunContext::detect_from(&env, "v1.2.0");
+        assert_eq!(deploy.trigger_type, TriggerType::Cli);
+    }
+
+    #[test]
+    fn detect_without_ci_uses_git() {
+        let env = env(&[("HOME", "/home/dev"), ("GITHUB_ACTIONS", "")]);
+
+        assert_eq!(CiProvider::detect_from(&env), None);
+
+        let ctx = RunContext::detect_from(&env, &git());
+        assert_eq!(ctx.repo, "local/checkout");
+        assert_eq!(ctx.branch, "local-branch");
+        assert_eq!(ctx.commit, "0000000");
+        assert_eq!(ctx.username, "Local Author");
+        assert_eq!(ctx.scm_type, "GITHUB");
+        assert!(ctx.url.is_empty());
+
+        let deploy = DeployRunContext::detect_from(&env, "my-deployer 0.3.1");
+        assert_eq!(deploy.trigger_type, TriggerType::Cli);
+        assert_eq!(deploy.trigger_version, "my-deployer 0.3.1");
+
+        let ctx = RunContext::detect_from(&env, &GitInfo::default());
+        assert!(ctx.repo.is_empty() && ctx.branch.is_empty() && ctx.scm_type.is_empty());
+    }
+}
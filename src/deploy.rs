@@ -0,0 +1,391 @@
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use strum::Display;
+
+use crate::atlas::{
+    Client, MigrateApplyParams, MigrateLintParams, MigrateStatusParams, MigrateValidateParams,
+};
+use crate::atlas_models::{MigrateApply, MigrateStatus, SummaryReport};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum Phase {
+    #[strum(serialize = "version")]
+    Version,
+
+    #[strum(serialize = "validate")]
+    Validate,
+
+    #[strum(serialize = "status")]
+    Status,
+
+    #[strum(serialize = "lint")]
+    Lint,
+
+    #[strum(serialize = "apply")]
+    Apply,
+
+    #[strum(serialize = "verify")]
+    Verify,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhaseReport {
+    pub phase: Phase,
+    pub duration: Duration,
+    /// Set if the phase was not needed, e.g. linting with nothing pending.
+    pub skipped: bool,
+    pub error: Option<String>,
+}
+
+/// The outcome of a [`Deployment`]. Phases that never ran are missing from
+/// `phases`, and their results are `None`.
+#[derive(Debug, Clone, Default)]
+pub struct DeploymentReport {
+    pub phases: Vec<PhaseReport>,
+    pub version: Option<String>,
+    pub status_before: Option<MigrateStatus>,
+    pub lint: Option<SummaryReport>,
+    pub apply: Option<MigrateApply>,
+    pub status_after: Option<MigrateStatus>,
+    /// Why the deployment stopped early, if it did.
+    pub error: Option<String>,
+}
+impl DeploymentReport {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.phases.iter().map(|p| p.duration).sum()
+    }
+
+    pub fn phase(&self, phase: Phase) -> Option<&PhaseReport> {
+        self.phases.iter().find(|p| p.phase == phase)
+    }
+}
+
+/// A migration deploy: checks the atlas version, validates the migration
+/// directory, applies pending migrations and verifies the target version
+/// was reached. Phases without params are skipped.
+#[derive(Debug, Clone, Default)]
+pub struct Deployment {
+    /// The lowest atlas version allowed, e.g. `v0.24.0`.
+    pub min_version: Option<String>,
+    pub validate: Option<MigrateValidateParams>,
+    pub status: MigrateStatusParams,
+    /// Lints the pending files. `latest` defaults to the number of pending
+    /// files when unset. The deployment stops if a file or step could not
+    /// be analyzed; diagnostics alone do not stop it.
+    pub lint: Option<MigrateLintParams>,
+    /// The apply, including the exec order to run it with.
+    pub apply: MigrateApplyParams,
+}
+impl Deployment {
+    pub fn run(&self, client: &Client) -> DeploymentReport {
+        self.run_with_hook(client, |_, _| Ok(()))
+    }
+
+    /// Runs the deployment, calling `hook` after every successful phase with
+    /// the report so far. An error from the hook aborts the deployment
+    /// before the next phase starts.
+    pub fn run_with_hook<F>(&self, client: &Client, mut hook: F) -> DeploymentReport
+    where
+        F: FnMut(Phase, &DeploymentReport) -> anyhow::Result<()>,
+    {
+        let mut report = DeploymentReport::default();
+
+        let phases = [
+            Phase::Version,
+            Phase::Validate,
+            Phase::Status,
+            Phase::Lint,
+            Phase::Apply,
+            Phase::Verify,
+        ];
+
+        for phase in phases {
+            let start = Instant::now();
+            let result = self.run_phase(client, phase, &mut report);
+
+            let (skipped, error) = match result {
+                Ok(ran) => (!ran, None),
+                Err(e) => (false, Some(e.to_string())),
+            };
+
+            report.phases.push(PhaseReport {
+                phase,
+                duration: start.elapsed(),
+                skipped,
+                error: error.clone(),
+            });
+
+            if let Some(e) = error {
+                report.error = Some(format!("{} failed: {}", phase, e));
+                break;
+            }
+
+            if let Err(e) = hook(phase, &report) {
+                report.error = Some(format!("aborted after {}: {}", phase, e));
+                break;
+            }
+        }
+
+        report
+    }
+
+    /// Runs one phase, storing its result in `report`. Returns whether the
+    /// phase did anything.
+    fn run_phase(
+        &self,
+        client: &Client,
+        phase: Phase,
+        report: &mut DeploymentReport,
+    ) -> anyhow::Result<bool> {
+        match phase {
+            Phase::Version => {
+                let version = client.version()?;
+
+                if let Some(ref min) = self.min_version {
+                    if version_triple(&version) < version_triple(min) {
+                        return Err(anyhow!(
+                            "atlas {} is older than the required {}",
+                            version,
+                            min
+                        ));
+                    }
+                }
+
+                report.version = Some(version);
+            }
+            Phase::Validate => match self.validate {
+                Some(ref params) => client.migrate_validate(params.clone())?,
+                None => return Ok(false),
+            },
+            Phase::Status => {
+                let status = client.migrate_status(self.status.clone())?;
+                if !status.error.is_empty() {
+                    return Err(anyhow!("{}", status.error));
+                }
+
+                report.status_before = Some(status);
+            }
+            Phase::Lint => {
+                let pending = pending_count(report);
+
+                let mut params = match self.lint {
+                    Some(ref params) if pending > 0 => params.clone(),
+                    _ => return Ok(false),
+                };
+
+                if params.latest == 0 {
+                    params.latest = pending as u64;
+                }
+
+                let lint = client.migrate_lint(params)?;
+                let result = check_lint(&lint);
+                report.lint = Some(lint);
+                result?;
+            }
+            Phase::Apply => {
+                if pending_count(report) == 0 {
+                    return Ok(false);
+                }
+
+                let apply = client.migrate_apply(self.apply.clone())?;
+                let error = apply.error.clone();
+                report.apply = Some(apply);
+
+                if !error.is_empty() {
+                    return Err(anyhow!("{}", error));
+                }
+            }
+            Phase::Verify => {
+                let status = client.migrate_status(self.status.clone())?;
+                let result = verify(report, &status);
+                report.status_after = Some(status);
+                result?;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+fn pending_count(report: &DeploymentReport) -> usize {
+    report
+        .status_before
+        .as_ref()
+        .map(|s| s.pending.len())
+        .unwrap_or(0)
+}
+
+/// Fails if linting did not complete: a step or file that atlas could not
+/// analyze, e.g. a checksum mismatch or a statement the dev database
+/// rejected.
+fn check_lint(lint: &SummaryReport) -> anyhow::Result<()> {
+    let steps = lint
+        .steps
+        .iter()
+        .filter(|s| !s.error.is_empty())
+        .map(|s| format!("{}: {}", s.name, s.error));
+
+    let files = lint
+        .files
+        .iter()
+        .filter(|f| !f.error.is_empty())
+        .map(|f| format!("{}: {}", f.name, f.error));
+
+    let errors = steps.chain(files).collect::<Vec<String>>();
+    if !errors.is_empty() {
+        return Err(anyhow!("{}", errors.join("\n")));
+    }
+
+    Ok(())
+}
+
+/// Checks that the database reached the version the apply targeted, or is
+/// still where it was if nothing was applied.
+fn verify(report: &DeploymentReport, after: &MigrateStatus) -> anyhow::Result<()> {
+    if !after.error.is_empty() {
+        return Err(anyhow!("{}", after.error));
+    }
+
+    let expected = match (&report.apply, &report.status_before) {
+        (Some(apply), _) if !apply.target.is_empty() => &apply.target,
+        (_, Some(before)) => &before.current,
+        _ => return Ok(()),
+    };
+
+    if &after.current != expected {
+        return Err(anyhow!(
+            "database is at version {}, expected {}",
+            after.current,
+            expected
+        ));
+    }
+
+    Ok(())
+}
+
+/// The numeric `major.minor.patch` of a version such as `v0.24.1-canary`.
+/// Missing or malformed parts count as zero.
+fn version_triple(version: &str) -> (u64, u64, u64) {
+    let core = version
+        .trim_start_matches('v')
+        .split(['-', '+'])
+        .next()
+        .unwrap_or_default();
+
+    let mut parts = core.split('.').map(|p| p.parse::<u64>().unwrap_or(0));
+
+    (
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(current: &str, pending: usize) -> MigrateStatus {
+        let pending = (0..pending)
+            .map(|i| format!(r#"{{"Name": "{0}.sql", "Version": "{0}"}}"#, i + 2))
+            .collect::<Vec<String>>()
+            .join(",");
+
+        serde_json::from_str(&format!(
+            r#"{{"Current": "{}", "Pending": [{}], "Status": "PENDING"}}"#,
+            current, pending
+        ))
+        .unwrap()
+    }
+
+    fn apply(target: &str) -> MigrateApply {
+        serde_json::from_str(&format!(r#"{{"Current": "1", "Target": "{}"}}"#, target)).unwrap()
+    }
+
+    #[test]
+    fn verify_target_version() {
+        let report = DeploymentReport {
+            status_before: Some(status("1", 2)),
+            apply: Some(apply("3")),
+            ..DeploymentReport::default()
+        };
+
+        assert!(verify(&report, &status("3", 0)).is_ok());
+        assert_eq!(
+            verify(&report, &status("2", 1)).unwrap_err().to_string(),
+            "database is at version 2, expected 3"
+        );
+
+        // nothing was applied, the database must not have moved
+        let report = DeploymentReport {
+            status_before: Some(status("1", 0)),
+            ..DeploymentReport::default()
+        };
+        assert!(verify(&report, &status("1", 0)).is_ok());
+        assert!(verify(&report, &status("2", 0)).is_err());
+
+        // an apply without a target falls back to the version before
+        let report = DeploymentReport {
+            status_before: Some(status("1", 0)),
+            apply: Some(apply("")),
+            ..DeploymentReport::default()
+        };
+        assert!(verify(&report, &status("1", 0)).is_ok());
+
+        // nothing to compare with
+        assert!(verify(&DeploymentReport::default(), &status("7", 0)).is_ok());
+
+        let mut after = status("3", 0);
+        after.error = "connection refused".into();
+        assert_eq!(
+            verify(&DeploymentReport::default(), &after)
+                .unwrap_err()
+                .to_string(),
+            "connection refused"
+        );
+    }
+
+    #[test]
+    fn version_triples() {
+        let tests = [
+            ("v0.24.1", (0, 24, 1)),
+            ("0.24.1", (0, 24, 1)),
+            ("v0.24.1-6a4f0d3-canary", (0, 24, 1)),
+            ("v1.2.3+build.5", (1, 2, 3)),
+            ("v1.2", (1, 2, 0)),
+            ("v1", (1, 0, 0)),
+            ("", (0, 0, 0)),
+            ("canary", (0, 0, 0)),
+            ("v1.x.3", (1, 0, 3)),
+        ];
+
+        for (version, want) in tests {
+            assert_eq!(version_triple(version), want, "{}", version);
+        }
+
+        assert!(version_triple("v0.24.1") < version_triple("v0.100.0"));
+        assert!(version_triple("v0.24.1-canary") >= version_triple("v0.24.1"));
+    }
+
+    #[test]
+    fn lint_errors_fail() {
+        let lint: SummaryReport =
+            serde_json::from_str(include_str!("../testdata/lint.json")).unwrap();
+
+        let err = check_lint(&lint).unwrap_err().to_string();
+        assert!(err.starts_with("Migration Integrity Check: checksum mismatch"));
+        assert!(err.contains("\n20240510140000_add_age.sql: executing statement"));
+
+        // diagnostics alone pass
+        let mut lint = lint;
+        lint.steps.retain(|s| s.error.is_empty());
+        lint.files.iter_mut().for_each(|f| f.error.clear());
+        assert!(lint.diagnostics_count() > 0);
+        assert!(check_lint(&lint).is_ok());
+    }
+}
@@ -0,0 +1,566 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+
+use crate::atlas::{Client, MigrateHashParams};
+use crate::atlas_models::{SuggestedFix, SummaryReport};
+
+const DIFF_CONTEXT: usize = 3;
+
+/// A text edit suggested by `migrate lint`, resolved to the migration file it
+/// applies to. `line` and `end` are the 1-based, inclusive range of lines
+/// replaced by `new_text`; an `end` before `line` inserts `new_text` above
+/// `line` without replacing anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FixEdit {
+    pub file: String,
+    /// The analyzer code of the diagnostic the fix was suggested for. Fixes
+    /// suggested for a whole report carry the code of its first diagnostic.
+    pub code: String,
+    pub message: String,
+    pub line: usize,
+    pub end: usize,
+    pub new_text: String,
+}
+impl FixEdit {
+    fn replaced_lines(&self) -> usize {
+        (self.end + 1).saturating_sub(self.line)
+    }
+
+    fn overlaps(&self, other: &FixEdit) -> bool {
+        let (a_start, a_end) = (self.line, self.line + self.replaced_lines());
+        let (b_start, b_end) = (other.line, other.line + other.replaced_lines());
+
+        // two insertions at the same line would interleave unpredictably
+        (a_start == b_start) || (a_start < b_end && b_start < a_end)
+    }
+}
+
+/// The fixes planned for one migration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFix {
+    pub name: String,
+    pub path: PathBuf,
+    pub original: String,
+    pub fixed: String,
+    /// The edits applied to produce `fixed`, in file order.
+    pub applied: Vec<FixEdit>,
+    /// Edits dropped because they overlap an edit that was applied.
+    pub conflicts: Vec<FixEdit>,
+}
+
+/// The suggested fixes of a lint report, applied in memory. Nothing touches
+/// the disk until the plan is passed to [`Client::migrate_apply_fixes`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FixPlan {
+    pub files: Vec<FileFix>,
+}
+impl FixPlan {
+    /// Plans every suggested fix of `report` against the migration files in
+    /// `dir`.
+    pub fn from_summary_report(report: &SummaryReport, dir: &Path) -> anyhow::Result<Self> {
+        Self::from_summary_report_filtered(report, dir, |_| true)
+    }
+
+    /// Plans the suggested fixes of `report` accepted by `select`. When edits
+    /// overlap, the one earlier in the file wins and the rest are recorded as
+    /// conflicts. Fails if a file changed on disk since it was linted.
+    pub fn from_summary_report_filtered<F>(
+        report: &SummaryReport,
+        dir: &Path,
+        select: F,
+    ) -> anyhow::Result<Self>
+    where
+        F: Fn(&FixEdit) -> bool,
+    {
+        let mut files = Vec::new();
+
+        for file in &report.files {
+            let mut edits: Vec<FixEdit> = Vec::new();
+
+            for r in &file.reports {
+                let report_code = r
+                    .diagnostics
+                    .first()
+                    .map(|d| d.code.as_str())
+                    .unwrap_or_default();
+
+                let fixes = r.suggested_fixes.iter().map(|f| (report_code, f)).chain(
+                    r.diagnostics
+                        .iter()
+                        .flat_map(|d| d.suggested_fixes.iter().map(|f| (d.code.as_str(), f))),
+                );
+
+                for (code, fix) in fixes {
+                    let edit = match fix_edit(&file.name, code, fix) {
+                        Some(edit) => edit,
+                        None => continue,
+                    };
+
+                    // the same fix is often suggested for several diagnostics
+                    let duplicate = edits.iter().any(|e| {
+                        e.line == edit.line && e.end == edit.end && e.new_text == edit.new_text
+                    });
+
+                    if !duplicate && select(&edit) {
+                        edits.push(edit);
+                    }
+                }
+            }
+
+            if edits.is_empty() {
+                continue;
+            }
+
+            let path = dir.join(&file.name);
+            let original = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("failed to read migration file {}: {}", path.display(), e))?;
+
+            if !file.text.is_empty() && file.text != original {
+                return Err(anyhow!(
+                    "migration file {} changed since it was linted",
+                    path.display()
+                ));
+            }
+
+            files.push(FileFix::new(&file.name, path, original, edits));
+        }
+
+        Ok(Self { files })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.iter().all(|f| f.applied.is_empty())
+    }
+
+    pub fn conflicts(&self) -> impl Iterator<Item = &FixEdit> {
+        self.files.iter().flat_map(|f| f.conflicts.iter())
+    }
+
+    /// A unified diff of every planned change, as `diff -u` or `git diff`
+    /// would print it.
+    pub fn diff(&self) -> String {
+        self.files.iter().map(FileFix::diff).collect()
+    }
+}
+
+impl FileFix {
+    fn new(name: &str, path: PathBuf, original: String, mut edits: Vec<FixEdit>) -> Self {
+        edits.sort_by_key(|e| (e.line, e.end));
+
+        let mut applied: Vec<FixEdit> = Vec::new();
+        let mut conflicts = Vec::new();
+
+        for edit in edits {
+            if applied.iter().any(|a| a.overlaps(&edit)) {
+                conflicts.push(edit);
+            } else {
+                applied.push(edit);
+            }
+        }
+
+        let (lines, trailing_newline) = split_lines(&original);
+        let line_count = lines.len();
+
+        // edits past the end of the file append to it
+        let mut fixed: Vec<&str> = Vec::new();
+        let mut next = 0;
+        for edit in &applied {
+            let start = (edit.line - 1).min(line_count);
+            fixed.extend_from_slice(&lines[next..start]);
+            fixed.extend(text_lines(&edit.new_text));
+            next = (start + edit.replaced_lines()).min(line_count);
+        }
+        fixed.extend_from_slice(&lines[next..]);
+
+        // an edit that ends the file and its last line brings its newline
+        let ends_with_edit =
+            next == line_count && applied.last().is_some_and(|e| e.new_text.ends_with('\n'));
+
+        let mut fixed = fixed.join("\n");
+        if (trailing_newline || ends_with_edit) && !fixed.is_empty() {
+            fixed.push('\n');
+        }
+
+        Self {
+            name: name.into(),
+            path,
+            original,
+            fixed,
+            applied,
+            conflicts,
+        }
+    }
+
+    pub fn diff(&self) -> String {
+        if self.applied.is_empty() {
+            return String::new();
+        }
+
+        let (old, _) = split_lines(&self.original);
+        let mut out = format!("--- a/{}\n+++ b/{}\n", self.name, self.name);
+
+        // edits whose context would overlap share a hunk
+        let mut groups: Vec<Vec<&FixEdit>> = Vec::new();
+        for edit in &self.applied {
+            let start = (edit.line - 1).min(old.len());
+
+            let joins = groups.last().and_then(|g| g.last()).is_some_and(|last| {
+                let last_end = (last.line - 1).min(old.len()) + last.replaced_lines();
+                start <= last_end + 2 * DIFF_CONTEXT
+            });
+
+            match groups.last_mut() {
+                Some(group) if joins => group.push(edit),
+                _ => groups.push(vec![edit]),
+            }
+        }
+
+        // lines added minus lines removed by the hunks written so far
+        let mut offset: isize = 0;
+        for group in groups {
+            let first = (group[0].line - 1).min(old.len());
+            let hunk_start = first.saturating_sub(DIFF_CONTEXT);
+
+            let mut body = String::new();
+            let (mut old_len, mut new_len) = (0, 0);
+            let mut pos = hunk_start;
+
+            for edit in &group {
+                let start = (edit.line - 1).min(old.len());
+                let end = (start + edit.replaced_lines()).min(old.len());
+
+                for line in &old[pos..start] {
+                    body.push_str(&format!(" {}\n", line));
+                }
+                for line in &old[start..end] {
+                    body.push_str(&format!("-{}\n", line));
+                }
+                let inserted = text_lines(&edit.new_text);
+                for line in &inserted {
+                    body.push_str(&format!("+{}\n", line));
+                }
+
+                old_len += end - pos;
+                new_len += start - pos + inserted.len();
+                pos = end;
+            }
+
+            let hunk_end = (pos + DIFF_CONTEXT).min(old.len());
+            for line in &old[pos..hunk_end] {
+                body.push_str(&format!(" {}\n", line));
+            }
+            old_len += hunk_end - pos;
+            new_len += hunk_end - pos;
+
+            let new_start = hunk_start as isize + offset;
+            out.push_str(&format!(
+                "@@ -{} +{} @@\n{}",
+                hunk_range(hunk_start, old_len),
+                hunk_range(new_start.max(0) as usize, new_len),
+                body
+            ));
+
+            offset += new_len as isize - old_len as isize;
+        }
+
+        out
+    }
+}
+
+impl Client {
+    /// Writes the fixed migration files of `plan` and re-hashes the
+    /// migration directory so `atlas.sum` matches the new contents.
+    ///
+    /// Every file is staged next to its target before any is replaced, so a
+    /// failed write leaves the directory untouched. If replacing the files
+    /// fails partway, the directory is still re-hashed so that `atlas.sum`
+    /// matches whatever is on disk, and the error is returned.
+    pub fn migrate_apply_fixes(
+        &self,
+        plan: &FixPlan,
+        params: MigrateHashParams,
+    ) -> anyhow::Result<()> {
+        if plan.is_empty() {
+            return Ok(());
+        }
+
+        let staged = stage_files(plan)?;
+
+        if let Err(e) = replace_files(&staged) {
+            return match self.migrate_hash(params) {
+                Ok(()) => Err(e),
+                Err(hash_err) => Err(anyhow!(
+                    "{}; re-hashing the migration directory also failed: {}",
+                    e,
+                    hash_err
+                )),
+            };
+        }
+
+        self.migrate_hash(params)
+    }
+}
+
+/// A fixed file written to a temporary path beside the file it replaces.
+struct StagedFile<'a> {
+    file: &'a FileFix,
+    tmp: PathBuf,
+}
+
+/// Writes the fixed contents of every file of `plan` to a temporary file in
+/// the same directory. Fails, removing what was staged, if a file changed
+/// since it was planned or cannot be written.
+fn stage_files(plan: &FixPlan) -> anyhow::Result<Vec<StagedFile<'_>>> {
+    let mut staged: Vec<StagedFile> = Vec::new();
+
+    let result = plan
+        .files
+        .iter()
+        .filter(|f| !f.applied.is_empty())
+        .try_for_each(|file| {
+            let current = std::fs::read_to_string(&file.path).map_err(|e| {
+                anyhow!(
+                    "failed to read migration file {}: {}",
+                    file.path.display(),
+                    e
+                )
+            })?;
+
+            if current != file.original {
+                return Err(anyhow!(
+                    "migration file {} changed since the fixes were planned",
+                    file.path.display()
+                ));
+            }
+
+            let tmp = file.path.with_file_name(format!(".{}.fix", file.name));
+            std::fs::write(&tmp, &file.fixed)
+                .map_err(|e| anyhow!("failed to write {}: {}", tmp.display(), e))?;
+
+            staged.push(StagedFile { file, tmp });
+            Ok(())
+        });
+
+    if let Err(e) = result {
+        for s in &staged {
+            let _ = std::fs::remove_file(&s.tmp);
+        }
+        return Err(e);
+    }
+
+    Ok(staged)
+}
+
+/// Moves the staged files over the originals. Renames within a directory
+/// are atomic, so each file is either fully fixed or untouched.
+fn replace_files(staged: &[StagedFile]) -> anyhow::Result<()> {
+    let mut result = Ok(());
+
+    for s in staged {
+        if result.is_err() {
+            let _ = std::fs::remove_file(&s.tmp);
+            continue;
+        }
+
+        if let Err(e) = std::fs::rename(&s.tmp, &s.file.path) {
+            let _ = std::fs::remove_file(&s.tmp);
+            result = Err(anyhow!(
+                "failed to write migration file {}: {}",
+                s.file.path.display(),
+                e
+            ));
+        }
+    }
+
+    result
+}
+
+fn fix_edit(file: &str, code: &str, fix: &SuggestedFix) -> Option<FixEdit> {
+    let edit = fix.text_edit.as_ref()?;
+    let line = usize::try_from(edit.line).ok().filter(|l| *l > 0)?;
+
+    Some(FixEdit {
+        file: file.into(),
+        code: code.into(),
+        message: fix.message.clone(),
+        line,
+        end: usize::try_from(edit.end).unwrap_or(0),
+        new_text: edit.new_text.clone(),
+    })
+}
+
+fn split_lines(text: &str) -> (Vec<&str>, bool) {
+    let trailing_newline = text.ends_with('\n');
+    let body = text.strip_suffix('\n').unwrap_or(text);
+
+    match body.is_empty() && !trailing_newline {
+        true => (Vec::new(), false),
+        false => (body.split('\n').collect(), trailing_newline),
+    }
+}
+
+fn text_lines(text: &str) -> Vec<&str> {
+    match text.is_empty() {
+        true => Vec::new(),
+        false => text
+            .strip_suffix('\n')
+            .unwrap_or(text)
+            .split('\n')
+            .collect(),
+    }
+}
+
+/// The `start,len` part of a hunk header. Empty ranges point at the line
+/// before them, as diff does.
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        len => format!("{},{}", start + 1, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(line: usize, end: usize, new_text: &str) -> FixEdit {
+        FixEdit {
+            file: "1_init.sql".into(),
+            code: "PG101".into(),
+            message: String::new(),
+            line,
+            end,
+            new_text: new_text.into(),
+        }
+    }
+
+    fn fix(original: &str, edits: Vec<FixEdit>) -> FileFix {
+        FileFix::new(
+            "1_init.sql",
+            PathBuf::from("1_init.sql"),
+            original.into(),
+            edits,
+        )
+    }
+
+    const SQL: &str = "CREATE TABLE a (id int);\nCREATE TABLE b (id int);\nCREATE INDEX i ON a (id);\nCREATE INDEX j ON b (id);\n";
+
+    #[test]
+    fn overlapping_edits_conflict() {
+        let f = fix(SQL, vec![edit(3, 4, "-- x\n"), edit(2, 3, "-- y\n")]);
+        assert_eq!(f.applied, vec![edit(2, 3, "-- y\n")]);
+        assert_eq!(f.conflicts, vec![edit(3, 4, "-- x\n")]);
+        assert_eq!(
+            f.fixed,
+            "CREATE TABLE a (id int);\n-- y\nCREATE INDEX j ON b (id);\n"
+        );
+
+        // an insertion and a replacement at the same line
+        let f = fix(SQL, vec![edit(3, 3, "-- x\n"), edit(3, 2, "-- y\n")]);
+        assert_eq!(f.applied.len(), 1);
+        assert_eq!(f.conflicts.len(), 1);
+
+        // adjacent lines do not overlap
+        let f = fix(SQL, vec![edit(2, 2, "-- b\n"), edit(3, 3, "-- i\n")]);
+        assert!(f.conflicts.is_empty());
+        assert_eq!(
+            f.fixed,
+            "CREATE TABLE a (id int);\n-- b\n-- i\nCREATE INDEX j ON b (id);\n"
+        );
+    }
+
+    #[test]
+    fn edits_at_file_boundaries() {
+        let f = fix(SQL, vec![edit(1, 0, "-- atlas:txmode none\n")]);
+        assert_eq!(f.fixed, format!("-- atlas:txmode none\n{}", SQL));
+
+        let f = fix(
+            SQL,
+            vec![edit(4, 4, "CREATE INDEX CONCURRENTLY j ON b (id);")],
+        );
+        assert!(f
+            .fixed
+            .ends_with("CREATE INDEX CONCURRENTLY j ON b (id);\n"));
+        assert_eq!(f.fixed.lines().count(), 4);
+
+        // past the end appends, keeping the missing trailing newline missing
+        let f = fix("SELECT 1;", vec![edit(2, 1, "SELECT 2;")]);
+        assert_eq!(f.fixed, "SELECT 1;\nSELECT 2;");
+
+        // inserting into an empty file keeps the inserted newline
+        let f = fix("", vec![edit(1, 0, "SELECT 1;\n")]);
+        assert_eq!(f.fixed, "SELECT 1;\n");
+
+        let f = fix("SELECT 1;", vec![edit(2, 1, "SELECT 2;\n")]);
+        assert_eq!(f.fixed, "SELECT 1;\nSELECT 2;\n");
+
+        let diff = fix(SQL, vec![edit(1, 1, "-- a\n"), edit(4, 4, "-- j\n")]).diff();
+        assert_eq!(
+            diff,
+            "--- a/1_init.sql\n+++ b/1_init.sql\n@@ -1,4 +1,4 @@\n-CREATE TABLE a (id int);\n+-- a\n CREATE TABLE b (id int);\n CREATE INDEX i ON a (id);\n-CREATE INDEX j ON b (id);\n+-- j\n"
+        );
+    }
+
+    #[test]
+    fn multibyte_content() {
+        let original = "-- créé par l'équipe 🚀\nCREATE TABLE «users» (name text DEFAULT 'Zoë');\nSELECT 'ok';\n";
+        let f = fix(
+            original,
+            vec![edit(
+                2,
+                2,
+                "CREATE TABLE «users» (name text DEFAULT 'Zoë' NOT NULL);\n",
+            )],
+        );
+
+        assert_eq!(
+            f.fixed,
+            "-- créé par l'équipe 🚀\nCREATE TABLE «users» (name text DEFAULT 'Zoë' NOT NULL);\nSELECT 'ok';\n"
+        );
+        assert_eq!(
+            f.diff(),
+            "--- a/1_init.sql\n+++ b/1_init.sql\n@@ -1,3 +1,3 @@\n -- créé par l'équipe 🚀\n-CREATE TABLE «users» (name text DEFAULT 'Zoë');\n+CREATE TABLE «users» (name text DEFAULT 'Zoë' NOT NULL);\n SELECT 'ok';\n"
+        );
+    }
+
+    #[test]
+    fn staging_failure_leaves_files_untouched() {
+        let dir = std::env::temp_dir().join(format!("atlas_exec_fixes_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let ok = FileFix::new(
+            "1_a.sql",
+            dir.join("1_a.sql"),
+            SQL.into(),
+            vec![edit(1, 1, "-- a\n")],
+        );
+        std::fs::write(&ok.path, SQL).unwrap();
+
+        // changed on disk after planning
+        let changed = FileFix::new(
+            "2_b.sql",
+            dir.join("2_b.sql"),
+            SQL.into(),
+            vec![edit(1, 1, "-- b\n")],
+        );
+        std::fs::write(&changed.path, "SELECT 1;\n").unwrap();
+
+        let plan = FixPlan {
+            files: vec![ok.clone(), changed],
+        };
+        assert!(stage_files(&plan).is_err());
+        assert_eq!(std::fs::read_to_string(&ok.path).unwrap(), SQL);
+        assert!(!dir.join(".1_a.sql.fix").exists());
+
+        let plan = FixPlan {
+            files: vec![ok.clone()],
+        };
+        replace_files(&stage_files(&plan).unwrap()).unwrap();
+        assert_eq!(std::fs::read_to_string(&ok.path).unwrap(), ok.fixed);
+        assert!(!dir.join(".1_a.sql.fix").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
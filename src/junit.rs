@@ -0,0 +1,354 @@
+use crate::atlas_models::{is_zero_time, AppliedFile, MigrateApply, SummaryReport};
+use crate::util::{file_path, line_col, local_dir};
+
+/// A JUnit XML report, the format most CI systems use to display test
+/// results.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct JUnitReport {
+    pub name: String,
+    pub suites: Vec<TestSuite>,
+}
+impl JUnitReport {
+    /// One test case per linted file, with a failure per diagnostic and an
+    /// error if the file could not be analyzed.
+    pub fn from_summary_report(report: &SummaryReport) -> Self {
+        let dir = local_dir(&report.env.dir);
+        let mut suite = TestSuite {
+            name: "migrate lint".into(),
+            ..TestSuite::default()
+        };
+
+        for file in &report.files {
+            let path = file_path(dir.as_deref(), &file.name);
+            let mut case = TestCase {
+                name: file.name.clone(),
+                classname: "atlas.lint".into(),
+                file: Some(path.clone()),
+                ..TestCase::default()
+            };
+
+            for r in &file.reports {
+                for d in &r.diagnostics {
+                    let location = match line_col(&file.text, usize::try_from(d.pos).unwrap_or(0)) {
+                        Some((line, col)) => format!("{}:{}:{}", path, line, col),
+                        None => path.clone(),
+                    };
+
+                    case.failures.push(Failure {
+                        kind: d.code.clone(),
+                        message: d.text.clone(),
+                        body: format!("{}: {}: {}", location, r.text, d.text),
+                    });
+                }
+            }
+
+            if !file.error.is_empty() {
+                case.error = Some(Failure {
+                    kind: "error".into(),
+                    message: file.error.clone(),
+                    body: file.error.clone(),
+                });
+            }
+
+            suite.cases.push(case);
+        }
+
+        let step_errors = report
+            .steps
+            .iter()
+            .filter(|s| !s.error.is_empty())
+            .map(|s| format!("{}: {}", s.name, s.error))
+            .collect::<Vec<String>>();
+
+        if !step_errors.is_empty() {
+            suite.system_err = Some(step_errors.join("\n"));
+        }
+
+        Self {
+            name: "atlas".into(),
+            suites: vec![suite],
+        }
+    }
+
+    /// One test case per pending file: applied files pass or fail with the
+    /// statement that broke them, files that were never reached are skipped.
+    pub fn from_migrate_apply(apply: &MigrateApply) -> Self {
+        let mut suite = TestSuite {
+            name: "migrate apply".into(),
+            time: seconds_between(&apply.start, &apply.end),
+            ..TestSuite::default()
+        };
+
+        for file in &apply.applied {
+            suite.cases.push(TestCase::from_applied_file(file));
+        }
+
+        for file in &apply.pending {
+            if apply.applied.iter().any(|a| a.file.name == file.name) {
+                continue;
+            }
+
+            suite.cases.push(TestCase {
+                name: file.name.clone(),
+                classname: "atlas.apply".into(),
+                skipped: true,
+                ..TestCase::default()
+            });
+        }
+
+        let file_failed = apply.applied.iter().any(|f| f.error.is_some());
+        if !apply.error.is_empty() && !file_failed {
+            suite.system_err = Some(apply.error.clone());
+        }
+
+        Self {
+            name: "atlas".into(),
+            suites: vec![suite],
+        }
+    }
+
+    pub fn to_xml(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+
+        out.push_str(&format!(
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\">\n",
+            escape_attr(&self.name),
+            self.suites.iter().map(TestSuite::tests).sum::<usize>(),
+            self.suites.iter().map(TestSuite::failures).sum::<usize>(),
+            self.suites.iter().map(TestSuite::errors).sum::<usize>(),
+            self.suites.iter().map(TestSuite::skipped).sum::<usize>(),
+        ));
+
+        for suite in &self.suites {
+            suite.write_xml(&mut out);
+        }
+
+        out.push_str("</testsuites>\n");
+        out
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestSuite {
+    pub name: String,
+    pub time: f64,
+    pub cases: Vec<TestCase>,
+    pub system_err: Option<String>,
+}
+impl TestSuite {
+    pub fn tests(&self) -> usize {
+        self.cases.len()
+    }
+
+    pub fn failures(&self) -> usize {
+        self.cases.iter().filter(|c| !c.failures.is_empty()).count()
+    }
+
+    pub fn errors(&self) -> usize {
+        let case_errors = self.cases.iter().filter(|c| c.error.is_some()).count();
+        case_errors + usize::from(self.system_err.is_some())
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.cases.iter().filter(|c| c.skipped).count()
+    }
+
+    fn write_xml(&self, out: &mut String) {
+        out.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            escape_attr(&self.name),
+            self.tests(),
+            self.failures(),
+            self.errors(),
+            self.skipped(),
+            self.time,
+        ));
+
+        for case in &self.cases {
+            case.write_xml(out);
+        }
+
+        if let Some(ref err) = self.system_err {
+            out.push_str(&format!("    <system-err>{}</system-err>\n", escape(err)));
+        }
+
+        out.push_str("  </testsuite>\n");
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub classname: String,
+    pub file: Option<String>,
+    pub time: f64,
+    pub failures: Vec<Failure>,
+    pub error: Option<Failure>,
+    pub skipped: bool,
+}
+impl TestCase {
+    fn from_applied_file(file: &AppliedFile) -> Self {
+        let mut case = TestCase {
+            name: file.file.name.clone(),
+            classname: "atlas.apply".into(),
+            time: seconds_between(&file.start, &file.end),
+            ..TestCase::default()
+        };
+
+        if let Some(ref err) = file.error {
+            case.failures.push(Failure {
+                kind: "sql".into(),
+                message: err.error.clone(),
+                body: format!("{}\n\n{}", err.error, err.sql),
+            });
+        }
+
+        case
+    }
+
+    fn write_xml(&self, out: &mut String) {
+        out.push_str(&format!(
+            "    <testcase name=\"{}\" classname=\"{}\"",
+            escape_attr(&self.name),
+            escape_attr(&self.classname)
+        ));
+
+        if let Some(ref file) = self.file {
+            out.push_str(&format!(" file=\"{}\"", escape_attr(file)));
+        }
+
+        out.push_str(&format!(" time=\"{:.3}\"", self.time));
+
+        if self.failures.is_empty() && self.error.is_none() && !self.skipped {
+            out.push_str("/>\n");
+            return;
+        }
+
+        out.push_str(">\n");
+
+        for failure in &self.failures {
+            failure.write_xml("failure", out);
+        }
+
+        if let Some(ref error) = self.error {
+            error.write_xml("error", out);
+        }
+
+        if self.skipped {
+            out.push_str("      <skipped/>\n");
+        }
+
+        out.push_str("    </testcase>\n");
+    }
+}
+
+/// A `<failure>` or `<error>` element.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Failure {
+    pub kind: String,
+    pub message: String,
+    pub body: String,
+}
+impl Failure {
+    fn write_xml(&self, element: &str, out: &mut String) {
+        out.push_str(&format!(
+            "      <{} type=\"{}\" message=\"{}\">{}</{}>\n",
+            element,
+            escape_attr(&self.kind),
+            escape_attr(&self.message),
+            escape(&self.body),
+            element
+        ));
+    }
+}
+
+fn seconds_between(start: &time::OffsetDateTime, end: &time::OffsetDateTime) -> f64 {
+    if is_zero_time(start) || is_zero_time(end) {
+        return 0.0;
+    }
+
+    (*end - *start).as_seconds_f64().max(0.0)
+}
+
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // characters XML 1.0 cannot represent at all
+            c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Escapes an attribute value. Unlike element text, attribute values have
+/// their whitespace normalized by XML parsers, so line breaks and tabs are
+/// written as character references to survive.
+fn escape_attr(s: &str) -> String {
+    escape(s)
+        .replace('\t', "&#9;")
+        .replace('\n', "&#10;")
+        .replace('\r', "&#13;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_summary_report_golden() {
+        let report: SummaryReport =
+            serde_json::from_str(include_str!("../testdata/lint.json")).unwrap();
+
+        assert_eq!(
+            JUnitReport::from_summary_report(&report).to_xml(),
+            include_str!("../testdata/lint.junit.xml")
+        );
+    }
+
+    #[test]
+    fn from_migrate_apply_golden() {
+        let apply: MigrateApply =
+            serde_json::from_str(include_str!("../testdata/apply_error.json")).unwrap();
+
+        assert_eq!(
+            JUnitReport::from_migrate_apply(&apply).to_xml(),
+            include_str!("../testdata/apply_error.junit.xml")
+        );
+    }
+
+    #[test]
+    fn apply_error_without_failed_file_is_a_suite_error() {
+        let apply: MigrateApply = serde_json::from_str(
+            r#"{"Current": "20240510120000", "Error": "sql/migrate: connection refused"}"#,
+        )
+        .unwrap();
+
+        let report = JUnitReport::from_migrate_apply(&apply);
+        assert_eq!(report.suites[0].errors(), 1);
+        assert!(report
+            .to_xml()
+            .contains("<system-err>sql/migrate: connection refused</system-err>"));
+    }
+
+    #[test]
+    fn escapes_text_and_attributes() {
+        assert_eq!(
+            escape("a < b && c > \"d\" 'e'"),
+            "a &lt; b &amp;&amp; c &gt; &quot;d&quot; &apos;e&apos;"
+        );
+        assert_eq!(escape("tab\there\nnul\u{0}bell\u{7}"), "tab\there\nnulbell");
+        assert_eq!(
+            escape_attr("line 1\r\nline\t2"),
+            "line 1&#13;&#10;line&#9;2"
+        );
+    }
+}
@@ -1,3 +1,17 @@
+pub mod annotations;
+pub mod approval;
 pub mod atlas;
 pub mod atlas_models;
+pub mod builders;
+pub mod ci;
+pub mod database_url;
+pub mod deploy;
+pub mod fanout;
+pub mod fixes;
+pub mod junit;
+pub mod policy;
+pub mod render;
+pub mod rollback;
+pub mod sarif;
+pub mod stream;
 pub mod util;
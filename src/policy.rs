@@ -0,0 +1,756 @@
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use strum::Display;
+use thiserror::Error;
+
+use crate::approval::SchemaApplyPlan;
+use crate::atlas::{Client, SchemaApplyParams};
+use crate::atlas_models::{Env, MigrateApply, SchemaApply};
+use crate::database_url::Driver;
+
+/// Rules that planned SQL statements must satisfy before they are applied.
+/// The default policy allows everything.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    pub deny_drop_table: bool,
+    pub deny_drop_column: bool,
+    /// Require `CREATE INDEX CONCURRENTLY`, so Postgres does not lock the
+    /// table against writes while the index is built. Only checked when the
+    /// statements are known to target Postgres; no other driver has it.
+    pub require_concurrent_index: bool,
+    /// The schemas statements may touch. Empty allows any schema. Only
+    /// schema qualified names can be checked, unqualified objects pass.
+    pub allowed_schemas: Vec<String>,
+    pub max_statements: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum Rule {
+    #[strum(serialize = "drop-table")]
+    DropTable,
+
+    #[strum(serialize = "drop-column")]
+    DropColumn,
+
+    #[strum(serialize = "concurrent-index")]
+    ConcurrentIndex,
+
+    #[strum(serialize = "allowed-schemas")]
+    AllowedSchemas,
+
+    #[strum(serialize = "max-statements")]
+    MaxStatements,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub rule: Rule,
+    /// The migration file the statement belongs to, when checking migrations.
+    pub file: Option<String>,
+    /// The offending statement, `None` for rules about the plan as a whole.
+    pub statement: Option<String>,
+    pub message: String,
+}
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] ", self.rule)?;
+
+        if let Some(ref file) = self.file {
+            write!(f, "{}: ", file)?;
+        }
+
+        write!(f, "{}", self.message)?;
+
+        if let Some(ref stmt) = self.statement {
+            write!(f, ": {}", stmt.trim())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("{}", self.err_string())]
+pub struct PolicyError {
+    pub violations: Vec<Violation>,
+}
+impl PolicyError {
+    pub fn new(violations: Vec<Violation>) -> Self {
+        Self { violations }
+    }
+
+    pub fn err_string(&self) -> String {
+        let violations = self
+            .violations
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>();
+
+        format!("policy violated:\n{}", violations.join("\n"))
+    }
+}
+
+impl Policy {
+    /// Checks statements for `driver`. Driver specific rules are skipped if
+    /// it is `None`.
+    pub fn check_statements<S: AsRef<str>>(
+        &self,
+        statements: &[S],
+        driver: Option<Driver>,
+    ) -> Vec<Violation> {
+        let mut violations = Vec::new();
+
+        for stmt in statements {
+            self.check_statement(stmt.as_ref(), None, driver, &mut violations);
+        }
+
+        self.check_count(statements.len(), &mut violations);
+        violations
+    }
+
+    /// Checks the pending statements of a `schema apply` dry run.
+    pub fn check_schema_apply(&self, apply: &SchemaApply) -> Vec<Violation> {
+        self.check_statements(&apply.changes.pending, env_driver(&apply.env))
+    }
+
+    /// Checks the statements of a saved `schema apply` plan.
+    pub fn check_schema_apply_plan(&self, plan: &SchemaApplyPlan) -> Vec<Violation> {
+        self.check_statements(&plan.pending(), env_driver(&plan.schema_plan().env))
+    }
+
+    /// Checks the statements of a `migrate apply` dry run. Atlas lists the
+    /// statements of pending files under `applied` when nothing is executed.
+    /// The result does not name the driver, so it is passed as `driver`.
+    pub fn check_migrate_apply(
+        &self,
+        apply: &MigrateApply,
+        driver: Option<Driver>,
+    ) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let mut count = 0;
+
+        for file in &apply.applied {
+            for stmt in &file.applied {
+                self.check_statement(stmt, Some(&file.file.name), driver, &mut violations);
+            }
+
+            count += file.applied.len();
+        }
+
+        self.check_count(count, &mut violations);
+        violations
+    }
+
+    fn check_count(&self, count: usize, violations: &mut Vec<Violation>) {
+        if let Some(max) = self.max_statements {
+            if count > max {
+                violations.push(Violation {
+                    rule: Rule::MaxStatements,
+                    file: None,
+                    statement: None,
+                    message: format!("{} statements planned, at most {} allowed", count, max),
+                });
+            }
+        }
+    }
+
+    fn check_statement(
+        &self,
+        stmt: &str,
+        file: Option<&str>,
+        driver: Option<Driver>,
+        violations: &mut Vec<Violation>,
+    ) {
+        let tokens = tokenize(stmt);
+        let mut violate = |rule: Rule, message: String| {
+            violations.push(Violation {
+                rule,
+                file: file.map(String::from),
+                statement: Some(stmt.to_string()),
+                message,
+            })
+        };
+
+        let kw = |i: usize| tokens.get(i).and_then(Token::keyword);
+        let has_kw_pair = |a: &str, b: &str| {
+            (0..tokens.len())
+                .any(|i| kw(i).as_deref() == Some(a) && kw(i + 1).as_deref() == Some(b))
+        };
+
+        let (verb, object) = (kw(0).unwrap_or_default(), kw(1).unwrap_or_default());
+
+        if self.deny_drop_table && verb == "DROP" && object == "TABLE" {
+            violate(Rule::DropTable, "dropping tables is not allowed".into());
+        }
+
+        if self.deny_drop_column
+            && verb == "ALTER"
+            && object == "TABLE"
+            && has_kw_pair("DROP", "COLUMN")
+        {
+            violate(Rule::DropColumn, "dropping columns is not allowed".into());
+        }
+
+        if self.require_concurrent_index && driver == Some(Driver::Postgres) && verb == "CREATE" {
+            let index = match object.as_str() {
+                "INDEX" => Some(2),
+                "UNIQUE" if kw(2).as_deref() == Some("INDEX") => Some(3),
+                _ => None,
+            };
+
+            if let Some(i) = index {
+                if kw(i).as_deref() != Some("CONCURRENTLY") {
+                    violate(
+                        Rule::ConcurrentIndex,
+                        "indexes must be created CONCURRENTLY".into(),
+                    );
+                }
+            }
+        }
+
+        if !self.allowed_schemas.is_empty() {
+            let denied = schemas(&tokens)
+                .into_iter()
+                .filter(|s| !self.allowed_schemas.iter().any(|a| a == s))
+                .collect::<Vec<String>>();
+
+            if !denied.is_empty() {
+                violate(
+                    Rule::AllowedSchemas,
+                    format!("schema {} is not allowed", denied.join(", ")),
+                );
+            }
+        }
+    }
+}
+
+impl Client {
+    /// Plans `schema apply`, checks the planned statements against `policy`
+    /// and applies them only if no rule is violated. Violations are returned
+    /// as a [`PolicyError`]. The checked plan is applied as saved, see
+    /// [`Client::execute_schema_apply`], so statements that were never
+    /// checked cannot run. A dry run in `params` stops after the check and
+    /// returns the planned changes.
+    pub fn schema_apply_with_policy(
+        &self,
+        params: SchemaApplyParams,
+        policy: &Policy,
+    ) -> anyhow::Result<SchemaApply> {
+        if params.dry_run {
+            let planned = self.schema_apply(params)?;
+
+            let violations = policy.check_schema_apply(&planned);
+            if !violations.is_empty() {
+                return Err(PolicyError::new(violations).into());
+            }
+
+            return Ok(planned);
+        }
+
+        let plan = self.plan_schema_apply(params)?;
+
+        let violations = policy.check_schema_apply_plan(&plan);
+        if !violations.is_empty() {
+            if let Some(url) = plan.plan_url() {
+                self.remove_plan_file(&url);
+            }
+
+            return Err(PolicyError::new(violations).into());
+        }
+
+        self.execute_schema_apply(plan)
+    }
+}
+
+/// The driver of the database a result was planned for.
+fn env_driver(env: &Env) -> Option<Driver> {
+    env.url
+        .as_ref()
+        .and_then(|url| url.driver())
+        .or_else(|| Driver::from_str(&env.driver).ok())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Token {
+    Word(String),
+    /// A quoted identifier, without its quotes.
+    Quoted(String),
+    Punct(char),
+}
+impl Token {
+    pub(crate) fn keyword(&self) -> Option<String> {
+        match self {
+            Token::Word(w) => Some(w.to_uppercase()),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        match self {
+            Token::Word(w) | Token::Quoted(w) => Some(w),
+            Token::Punct(_) => None,
+        }
+    }
+}
+
+/// Splits a statement into words, quoted identifiers and punctuation,
+/// dropping comments and string literals.
+pub(crate) fn tokenize(stmt: &str) -> Vec<Token> {
+    let chars = stmt.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        match c {
+            c if c.is_whitespace() => i += 1,
+            '-' if chars.get(i + 1) == Some(&'-') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '#' => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    i += 1;
+                }
+                i += 2;
+            }
+            '\'' => {
+                i += 1;
+                while i < chars.len() {
+                    // '' escapes a quote inside a literal
+                    if chars[i] == '\'' && chars.get(i + 1) != Some(&'\'') {
+                        break;
+                    }
+                    i += if chars[i] == '\'' { 2 } else { 1 };
+                }
+                i += 1;
+            }
+            '"' | '`' | '[' => {
+                let close = if c == '[' { ']' } else { c };
+                let start = i + 1;
+                i = start;
+                while i < chars.len() && chars[i] != close {
+                    i += 1;
+                }
+                tokens.push(Token::Quoted(
+                    chars[start..i.min(chars.len())].iter().collect(),
+                ));
+                i += 1;
+            }
+            c if c.is_alphanumeric() || c == '_' || c == '$' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+                {
+                    i += 1;
+                }
+                tokens.push(Token::Word(chars[start..i].iter().collect()));
+            }
+            c => {
+                tokens.push(Token::Punct(c));
+                i += 1;
+            }
+        }
+    }
+
+    tokens
+}
+
+/// The schemas named by a statement: schemas created, altered or dropped
+/// directly, and the schema part of qualified object names.
+fn schemas(tokens: &[Token]) -> BTreeSet<String> {
+    const OBJECTS: &[&str] = &[
+        "TABLE",
+        "VIEW",
+        "INDEX",
+        "ON",
+        "REFERENCES",
+        "TYPE",
+        "SEQUENCE",
+        "FUNCTION",
+        "PROCEDURE",
+        "TRIGGER",
+        "INTO",
+        "FROM",
+        "UPDATE",
+    ];
+    const MODIFIERS: &[&str] = &["IF", "NOT", "EXISTS", "ONLY", "CONCURRENTLY"];
+
+    let mut schemas = BTreeSet::new();
+
+    for (i, token) in tokens.iter().enumerate() {
+        let keyword = match token.keyword() {
+            Some(k) => k,
+            None => continue,
+        };
+
+        let mut j = i + 1;
+        while tokens
+            .get(j)
+            .and_then(Token::keyword)
+            .is_some_and(|k| MODIFIERS.contains(&k.as_str()))
+        {
+            j += 1;
+        }
+
+        let name = qualified_name(&tokens[j.min(tokens.len())..]);
+
+        if keyword == "SCHEMA" || keyword == "DATABASE" {
+            if let Some(schema) = name.first() {
+                schemas.insert(schema.to_string());
+            }
+        } else if OBJECTS.contains(&keyword.as_str()) && name.len() > 1 {
+            schemas.insert(name[name.len() - 2].to_string());
+        }
+    }
+
+    schemas
+}
+
+/// The parts of the dotted name at the start of `tokens`.
+fn qualified_name(tokens: &[Token]) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut i = 0;
+
+    while let Some(part) = tokens.get(i).and_then(Token::name) {
+        parts.push(part);
+
+        if tokens.get(i + 1) != Some(&Token::Punct('.')) {
+            break;
+        }
+
+        i += 2;
+    }
+
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_index_only_for_postgres() {
+        let policy = Policy {
+            require_concurrent_index: true,
+            ..Policy::default()
+        };
+        let stmts = [
+            "CREATE INDEX `i` ON `t` (`c`);",
+            "CREATE UNIQUE INDEX CONCURRENTLY \"u\" ON \"t\" (\"c\");",
+        ];
+
+        let violations = policy.check_statements(&stmts, Some(Driver::Postgres));
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, Rule::ConcurrentIndex);
+        assert_eq!(violations[0].statement.as_deref(), Some(stmts[0]));
+
+        for driver in [
+            Some(Driver::MySql),
+            Some(Driver::Sqlite),
+            Some(Driver::ClickHouse),
+            None,
+        ] {
+            assert!(policy.check_statements(&stmts, driver).is_empty());
+        }
+    }
+
+    #[test]
+    fn driver_from_env() {
+        let apply: SchemaApply = serde_json::from_str(
+            r#"{"Driver": "mysql", "Changes": {"Pending": ["CREATE INDEX i ON t (c);"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(env_driver(&apply.env), Some(Driver::MySql));
+
+        let policy = Policy {
+            require_concurrent_index: true,
+            ..Policy::default()
+        };
+        assert!(policy.check_schema_apply(&apply).is_empty());
+
+        let apply: SchemaApply = serde_json::from_str(
+            r#"{"Driver": "postgres", "Changes": {"Pending": ["CREATE INDEX i ON t (c);"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(policy.check_schema_apply(&apply).len(), 1);
+    }
+
+    fn rules(policy: &Policy, stmt: &str) -> Vec<Rule> {
+        policy
+            .check_statements(&[stmt], Some(Driver::Postgres))
+            .into_iter()
+            .map(|v| v.rule)
+            .collect()
+    }
+
+    #[test]
+    fn deny_drop_table() {
+        let policy = Policy {
+            deny_drop_table: true,
+            ..Policy::default()
+        };
+        let tests = [
+            ("DROP TABLE \"users\";", true),
+            ("drop table if exists users;", true),
+            ("/* cleanup */ DROP TABLE `public`.`users`;", true),
+            ("-- DROP TABLE users\nSELECT 1;", false),
+            ("DROP VIEW users_view;", false),
+            ("DROP INDEX users_table;", false),
+            ("ALTER TABLE users DROP COLUMN email;", false),
+            ("COMMENT ON TABLE users IS 'DROP TABLE users';", false),
+        ];
+
+        for (stmt, denied) in tests {
+            assert_eq!(
+                rules(&policy, stmt) == [Rule::DropTable],
+                denied,
+                "{}",
+                stmt
+            );
+        }
+
+        assert!(rules(&Policy::default(), "DROP TABLE users;").is_empty());
+    }
+
+    #[test]
+    fn deny_drop_column() {
+        let policy = Policy {
+            deny_drop_column: true,
+            ..Policy::default()
+        };
+        let tests = [
+            ("ALTER TABLE users DROP COLUMN email;", true),
+            (
+                "alter table \"users\" add column age int, drop column \"email\";",
+                true,
+            ),
+            ("ALTER TABLE users\n  -- keep\n  DROP COLUMN email;", true),
+            ("ALTER TABLE users DROP CONSTRAINT users_email_key;", false),
+            (
+                "ALTER TABLE users ADD COLUMN \"drop\" int, ADD COLUMN \"column\" int;",
+                false,
+            ),
+            (
+                "ALTER TABLE users ADD CHECK (note <> 'DROP COLUMN');",
+                false,
+            ),
+            ("DROP TABLE users;", false),
+        ];
+
+        for (stmt, denied) in tests {
+            assert_eq!(
+                rules(&policy, stmt) == [Rule::DropColumn],
+                denied,
+                "{}",
+                stmt
+            );
+        }
+    }
+
+    #[test]
+    fn allowed_schemas() {
+        let policy = Policy {
+            allowed_schemas: vec!["public".into(), "audit".into()],
+            ..Policy::default()
+        };
+        let tests = [
+            ("CREATE TABLE public.users (id int);", None),
+            ("CREATE TABLE users (id int);", None),
+            ("CREATE SCHEMA audit;", None),
+            ("CREATE SCHEMA IF NOT EXISTS billing;", Some("billing")),
+            ("DROP SCHEMA \"billing\" CASCADE;", Some("billing")),
+            (
+                "CREATE TABLE \"billing\".\"invoices\" (id int);",
+                Some("billing"),
+            ),
+            (
+                "CREATE TABLE IF NOT EXISTS billing.invoices (id int);",
+                Some("billing"),
+            ),
+            (
+                "CREATE INDEX i ON ONLY billing.invoices (id);",
+                Some("billing"),
+            ),
+            (
+                "ALTER TABLE public.users ADD FOREIGN KEY (org) REFERENCES crm.orgs (id);",
+                Some("crm"),
+            ),
+            (
+                "INSERT INTO billing.invoices SELECT * FROM crm.orgs;",
+                Some("billing, crm"),
+            ),
+            ("CREATE TABLE `db`.`public`.`t` (id int);", None),
+            (
+                "ALTER TABLE public.users ADD COLUMN note text DEFAULT 'crm.orgs';",
+                None,
+            ),
+        ];
+
+        for (stmt, denied) in tests {
+            let violations = policy.check_statements(&[stmt], None);
+            let message = violations.first().map(|v| v.message.clone());
+
+            assert_eq!(
+                message,
+                denied.map(|s| format!("schema {} is not allowed", s)),
+                "{}",
+                stmt
+            );
+        }
+    }
+
+    #[test]
+    fn schemas_and_qualified_names() {
+        let tokens = tokenize("ALTER TABLE \"a\".b RENAME TO c");
+        assert_eq!(qualified_name(&tokens[2..]), vec!["a", "b"]);
+        assert_eq!(qualified_name(&tokens[5..]), vec!["RENAME"]);
+        assert!(qualified_name(&[Token::Punct('(')]).is_empty());
+        assert!(qualified_name(&[]).is_empty());
+
+        let tokens = tokenize("CREATE TABLE [cat].[s].[t] (id int)");
+        assert_eq!(qualified_name(&tokens[2..]), vec!["cat", "s", "t"]);
+        assert_eq!(
+            schemas(&tokens).into_iter().collect::<Vec<String>>(),
+            vec!["s"]
+        );
+
+        // a statement ending in a keyword
+        assert!(schemas(&tokenize("DROP TABLE")).is_empty());
+    }
+
+    #[test]
+    fn max_statements() {
+        let policy = Policy {
+            max_statements: Some(2),
+            ..Policy::default()
+        };
+
+        assert!(policy
+            .check_statements(&["SELECT 1;", "SELECT 2;"], None)
+            .is_empty());
+
+        let violations = policy.check_statements(&["SELECT 1;", "SELECT 2;", "SELECT 3;"], None);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].rule, Rule::MaxStatements);
+        assert_eq!(violations[0].statement, None);
+        assert_eq!(
+            violations[0].to_string(),
+            "[max-statements] 3 statements planned, at most 2 allowed"
+        );
+
+        let policy = Policy {
+            max_statements: Some(0),
+            ..Policy::default()
+        };
+        assert!(policy.check_statements::<&str>(&[], None).is_empty());
+        assert_eq!(policy.check_statements(&["SELECT 1;"], None).len(), 1);
+    }
+
+    #[test]
+    fn check_migrate_apply() {
+        // migrate apply --dry-run --format '{{ json . }}'
+        let apply: MigrateApply = serde_json::from_str(
+            r#"{
+                "Driver": "postgres",
+                "Pending": [
+                    {"Name": "1_users.sql", "Version": "1"},
+                    {"Name": "2_cleanup.sql", "Version": "2"}
+                ],
+                "Applied": [
+                    {"Name": "1_users.sql", "Version": "1", "Skipped": 0, "Applied": ["CREATE TABLE users (id int);", "CREATE INDEX i ON users (id);"]},
+                    {"Name": "2_cleanup.sql", "Version": "2", "Skipped": 0, "Applied": ["ALTER TABLE users DROP COLUMN email;", "DROP TABLE legacy.users;"]}
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let policy = Policy {
+            deny_drop_table: true,
+            deny_drop_column: true,
+            require_concurrent_index: true,
+            allowed_schemas: vec!["public".into()],
+            max_statements: Some(3),
+        };
+
+        let violations = policy.check_migrate_apply(&apply, Some(Driver::Postgres));
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| (v.rule, v.file.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![
+                (Rule::ConcurrentIndex, Some("1_users.sql")),
+                (Rule::DropColumn, Some("2_cleanup.sql")),
+                (Rule::DropTable, Some("2_cleanup.sql")),
+                (Rule::AllowedSchemas, Some("2_cleanup.sql")),
+                (Rule::MaxStatements, None),
+            ]
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "[drop-column] 2_cleanup.sql: dropping columns is not allowed: ALTER TABLE users DROP COLUMN email;"
+        );
+
+        // the concurrent index rule needs the driver
+        assert_eq!(policy.check_migrate_apply(&apply, None).len(), 4);
+        assert!(Policy::default()
+            .check_migrate_apply(&apply, Some(Driver::Postgres))
+            .is_empty());
+    }
+
+    #[test]
+    fn tokenize_statements() {
+        let word = |w: &str| Token::Word(w.into());
+        let quoted = |w: &str| Token::Quoted(w.into());
+
+        let tests = [
+            (
+                "DROP TABLE t;",
+                vec![word("DROP"), word("TABLE"), word("t"), Token::Punct(';')],
+            ),
+            ("-- DROP TABLE t\nSELECT", vec![word("SELECT")]),
+            ("# mysql comment\nSELECT", vec![word("SELECT")]),
+            ("SELECT /* a\n * b */ 1", vec![word("SELECT"), word("1")]),
+            ("SELECT /* unterminated", vec![word("SELECT")]),
+            (
+                "SELECT 'it''s DROP TABLE' x",
+                vec![word("SELECT"), word("x")],
+            ),
+            ("SELECT '' , ''''", vec![word("SELECT"), Token::Punct(',')]),
+            ("SELECT 'unterminated", vec![word("SELECT")]),
+            (
+                "\"my table\".`col` [x y]",
+                vec![
+                    quoted("my table"),
+                    Token::Punct('.'),
+                    quoted("col"),
+                    quoted("x y"),
+                ],
+            ),
+            ("\"-- not a comment\"", vec![quoted("-- not a comment")]),
+            ("\"unterminated", vec![quoted("unterminated")]),
+            (
+                "$1 $$body$$ é_1",
+                vec![word("$1"), word("$$body$$"), word("é_1")],
+            ),
+            ("a-b", vec![word("a"), Token::Punct('-'), word("b")]),
+        ];
+
+        for (stmt, want) in tests {
+            assert_eq!(tokenize(stmt), want, "{}", stmt);
+        }
+
+        assert_eq!(word("drop").keyword().as_deref(), Some("DROP"));
+        assert_eq!(quoted("drop").keyword(), None);
+    }
+}
@@ -0,0 +1,503 @@
+use time::{Duration, OffsetDateTime};
+
+use crate::atlas_models::{
+    is_zero_time, MigrateApply, MigrateDown, MigrationStatus, SchemaApply, SqlError,
+};
+
+/// Renders command results for people: `to_text` mimics the output of the
+/// atlas CLI, `to_markdown` produces a summary for PR comments and chat.
+pub trait Render {
+    fn to_text(&self) -> String;
+
+    fn to_markdown(&self) -> String;
+}
+
+/// A migration file as the renderers see it, whether applied or reverted.
+struct Step<'a> {
+    name: &'a str,
+    version: &'a str,
+    description: &'a str,
+    duration: Option<Duration>,
+    statements: &'a [String],
+    error: Option<&'a SqlError>,
+}
+
+impl Render for MigrateApply {
+    fn to_text(&self) -> String {
+        let steps = applied_steps(self);
+
+        if steps.is_empty() && self.error.is_empty() {
+            return "No migration files to execute\n".into();
+        }
+
+        let mut out = match self.current.is_empty() {
+            true => format!("Migrating to version {}", self.target),
+            false => format!("Migrating to version {} from {}", self.target, self.current),
+        };
+        out.push_str(&format!(
+            " ({} migrations in total):\n\n",
+            self.pending.len().max(steps.len())
+        ));
+
+        write_steps_text(&mut out, "migrating", &steps);
+        write_totals_text(&mut out, &steps, duration_between(&self.start, &self.end));
+
+        if !self.error.is_empty() {
+            out.push_str(&format!("Error: {}\n", self.error));
+        }
+
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let steps = applied_steps(self);
+
+        let mut out = format!(
+            "### Migrate apply: `{}` → `{}`\n\n",
+            version_or_initial(&self.current),
+            version_or_initial(&self.target)
+        );
+
+        write_steps_markdown(&mut out, &steps, duration_between(&self.start, &self.end));
+
+        if !self.error.is_empty() && !steps.iter().any(|s| s.error.is_some()) {
+            out.push_str(&format!("\n**Error:** {}\n", self.error));
+        }
+
+        out
+    }
+}
+
+impl Render for MigrateDown {
+    fn to_text(&self) -> String {
+        if self
+            .status
+            .as_ref()
+            .is_some_and(MigrationStatus::needs_approval)
+        {
+            return format!(
+                "Migrating down from version {} to {} is pending approval, visit: {}\n",
+                self.current, self.target, self.url
+            );
+        }
+
+        let steps = reverted_steps(self);
+
+        if steps.is_empty() && self.error.is_empty() {
+            return "No migration files to revert\n".into();
+        }
+
+        let mut out = format!(
+            "Migrating down from version {} to {} ({} migrations in total):\n\n",
+            self.current,
+            version_or_initial(&self.target),
+            self.planned.len().max(steps.len())
+        );
+
+        write_steps_text(&mut out, "reverting", &steps);
+        write_totals_text(&mut out, &steps, duration_between(&self.start, &self.end));
+
+        if !self.error.is_empty() {
+            out.push_str(&format!("Error: {}\n", self.error));
+        }
+
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = format!(
+            "### Migrate down: `{}` → `{}`\n\n",
+            self.current,
+            version_or_initial(&self.target)
+        );
+
+        if self
+            .status
+            .as_ref()
+            .is_some_and(MigrationStatus::needs_approval)
+        {
+            out.push_str(&format!(
+                "Pending approval: [review the plan]({})\n",
+                self.url
+            ));
+            return out;
+        }
+
+        let steps = reverted_steps(self);
+
+        write_steps_markdown(&mut out, &steps, duration_between(&self.start, &self.end));
+
+        if !self.error.is_empty() && !steps.iter().any(|s| s.error.is_some()) {
+            out.push_str(&format!("\n**Error:** {}\n", self.error));
+        }
+
+        out
+    }
+}
+
+impl Render for SchemaApply {
+    fn to_text(&self) -> String {
+        let changes = &self.changes;
+        if changes.applied.is_empty() && changes.pending.is_empty() && changes.error.is_none() {
+            return match self.error.is_empty() {
+                true => "Schema is synced, no changes to be made\n".into(),
+                false => format!("Error: {}\n", self.error),
+            };
+        }
+
+        let mut out = String::new();
+
+        if !changes.applied.is_empty() || changes.error.is_some() {
+            out.push_str("-- Applied Changes:\n");
+
+            for stmt in &changes.applied {
+                out.push_str(&format!("    -> {}\n", one_line(stmt)));
+            }
+
+            if let Some(ref err) = changes.error {
+                out.push_str(&format!("    -> {}\n", one_line(&err.stmt)));
+                out.push_str(&format!("    Error: {}\n", err.text));
+            }
+        }
+
+        if !changes.pending.is_empty() {
+            out.push_str("-- Planned Changes:\n");
+
+            for stmt in &changes.pending {
+                out.push_str(&format!("    -> {}\n", one_line(stmt)));
+            }
+        }
+
+        if !self.error.is_empty() {
+            out.push_str(&format!("Error: {}\n", self.error));
+        }
+
+        out
+    }
+
+    fn to_markdown(&self) -> String {
+        let changes = &self.changes;
+        let mut out = String::from("### Schema apply\n\n");
+
+        if changes.applied.is_empty() && changes.pending.is_empty() && changes.error.is_none() {
+            match self.error.is_empty() {
+                true => out.push_str("Schema is synced, no changes to be made.\n"),
+                false => out.push_str(&format!("**Error:** {}\n", self.error)),
+            }
+            return out;
+        }
+
+        if !changes.applied.is_empty() {
+            out.push_str(&format!(
+                "**Applied** {} statement(s):\n\n{}\n\n",
+                changes.applied.len(),
+                code_block(&join_statements(&changes.applied))
+            ));
+        }
+
+        if !changes.pending.is_empty() {
+            out.push_str(&format!(
+                "**Planned** {} statement(s):\n\n{}\n\n",
+                changes.pending.len(),
+                code_block(&join_statements(&changes.pending))
+            ));
+        }
+
+        if let Some(ref err) = changes.error {
+            out.push_str(&format!(
+                "**Error:** {}\n\n{}\n",
+                err.text,
+                code_block(err.stmt.trim())
+            ));
+        } else if !self.error.is_empty() {
+            out.push_str(&format!("**Error:** {}\n", self.error));
+        }
+
+        out
+    }
+}
+
+fn applied_steps(apply: &MigrateApply) -> Vec<Step<'_>> {
+    apply
+        .applied
+        .iter()
+        .map(|f| Step {
+            name: &f.file.name,
+            version: &f.file.version,
+            description: &f.file.description,
+            duration: duration_between(&f.start, &f.end),
+            statements: &f.applied,
+            error: f.error.as_ref(),
+        })
+        .collect()
+}
+
+fn reverted_steps(down: &MigrateDown) -> Vec<Step<'_>> {
+    down.reverted
+        .iter()
+        .map(|f| Step {
+            name: &f.file.name,
+            version: &f.file.version,
+            description: &f.file.description,
+            duration: duration_between(&f.start, &f.end),
+            statements: &f.applied,
+            error: f.error.as_ref(),
+        })
+        .collect()
+}
+
+fn write_steps_text(out: &mut String, verb: &str, steps: &[Step]) {
+    for step in steps {
+        out.push_str(&format!("  -- {} version {}\n", verb, step.version));
+
+        let failing = step.error.map(|e| e.sql.trim());
+        for stmt in step.statements {
+            // the failing statement is printed along with its error below
+            if Some(stmt.trim()) == failing {
+                continue;
+            }
+
+            out.push_str(&format!("    -> {}\n", one_line(stmt)));
+        }
+
+        match step.error {
+            Some(err) => {
+                out.push_str(&format!("    -> {}\n", one_line(&err.sql)));
+                out.push_str(&format!("    Error: {}\n\n", err.error));
+            }
+            None => out.push_str(&format!(
+                "  -- ok ({})\n\n",
+                step.duration.map(format_duration).unwrap_or("-".into())
+            )),
+        }
+    }
+}
+
+fn write_totals_text(out: &mut String, steps: &[Step], total: Option<Duration>) {
+    let failed = steps.iter().filter(|s| s.error.is_some()).count();
+    let statements = steps.iter().map(|s| s.statements.len()).sum::<usize>();
+
+    out.push_str("  -------------------------\n");
+
+    if let Some(total) = total {
+        out.push_str(&format!("  -- {}\n", format_duration(total)));
+    }
+
+    match failed {
+        0 => {
+            out.push_str(&format!("  -- {}\n", plural(steps.len(), "migration")));
+            out.push_str(&format!("  -- {}\n", plural(statements, "sql statement")));
+        }
+        _ => {
+            out.push_str(&format!(
+                "  -- {} ok, {} with errors\n",
+                plural(steps.len() - failed, "migration"),
+                failed
+            ));
+            out.push_str(&format!(
+                "  -- {} ok, {} with errors\n",
+                plural(statements.saturating_sub(failed), "sql statement"),
+                failed
+            ));
+        }
+    }
+}
+
+fn write_steps_markdown(out: &mut String, steps: &[Step], total: Option<Duration>) {
+    if steps.is_empty() {
+        out.push_str("No migration files were executed.\n");
+        return;
+    }
+
+    out.push_str("| Status | Version | Description | Duration | Statements |\n");
+    out.push_str("|:------:|---------|-------------|---------:|-----------:|\n");
+
+    for step in steps {
+        out.push_str(&format!(
+            "| {} | `{}` | {} | {} | {} |\n",
+            if step.error.is_some() { "❌" } else { "✅" },
+            step.version,
+            escape_cell(step.description),
+            step.duration.map(format_duration).unwrap_or("-".into()),
+            step.statements.len()
+        ));
+    }
+
+    let statements = steps.iter().map(|s| s.statements.len()).sum::<usize>();
+    out.push_str(&format!(
+        "\n{}, {}",
+        plural(steps.len(), "migration"),
+        plural(statements, "statement")
+    ));
+
+    if let Some(total) = total {
+        out.push_str(&format!(" in {}", format_duration(total)));
+    }
+
+    out.push('\n');
+
+    for step in steps {
+        if let Some(err) = step.error {
+            out.push_str(&format!(
+                "\n**Error** in `{}`: {}\n\n{}\n",
+                step.name,
+                err.error,
+                code_block(err.sql.trim())
+            ));
+        }
+    }
+}
+
+fn duration_between(start: &OffsetDateTime, end: &OffsetDateTime) -> Option<Duration> {
+    if is_zero_time(start) || is_zero_time(end) {
+        return None;
+    }
+
+    Some(*end - *start)
+}
+
+/// Formats a duration the way Go's `time.Duration` prints, e.g. `1.5s`,
+/// `10.25ms` or `1m30s`, rounded to three decimals.
+pub fn format_duration(d: Duration) -> String {
+    let nanos = d.whole_nanoseconds();
+    let sign = if nanos < 0 { "-" } else { "" };
+    let nanos = nanos.unsigned_abs();
+
+    // rounds before picking the unit, so 999.9996ms prints as 1s rather
+    // than 1000ms
+    let round = |unit: u128| (nanos + unit / 2) / unit;
+
+    let formatted = match nanos {
+        0 => "0s".to_string(),
+        n if n < 1_000 => format!("{}ns", n),
+        n if n < 1_000_000 => format!("{}µs", thousandths(n)),
+        _ if round(1_000) < 1_000_000 => format!("{}ms", thousandths(round(1_000))),
+        _ => {
+            let millis = round(1_000_000);
+            let minutes = millis / 60_000;
+            let rest = thousandths(millis % 60_000);
+
+            match minutes {
+                0 => format!("{}s", rest),
+                m if m < 60 => format!("{}m{}s", m, rest),
+                m => format!("{}h{}m{}s", m / 60, m % 60, rest),
+            }
+        }
+    };
+
+    format!("{}{}", sign, formatted)
+}
+
+/// Formats `n` thousandths as a decimal without trailing zeros.
+fn thousandths(n: u128) -> String {
+    let s = format!("{}.{:03}", n / 1_000, n % 1_000);
+    s.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+fn version_or_initial(version: &str) -> &str {
+    match version.is_empty() {
+        true => "initial",
+        false => version,
+    }
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    match n {
+        1 => format!("1 {}", noun),
+        n => format!("{} {}s", n, noun),
+    }
+}
+
+fn one_line(stmt: &str) -> String {
+    stmt.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+fn join_statements(stmts: &[String]) -> String {
+    stmts
+        .iter()
+        .map(|s| s.trim())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// A fenced SQL code block. The fence is longer than any run of backticks
+/// in `sql`, so statements containing ``` cannot close it early.
+fn code_block(sql: &str) -> String {
+    let longest = sql.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest.max(2) + 1);
+
+    format!("{}sql\n{}\n{}", fence, sql, fence)
+}
+
+fn escape_cell(s: &str) -> String {
+    s.replace('|', "\\|").replace('\n', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::atlas_models::SchemaApply;
+
+    #[test]
+    fn migrate_apply_golden() {
+        let apply: MigrateApply =
+            serde_json::from_str(include_str!("../testdata/apply_error.json")).unwrap();
+
+        assert_eq!(apply.to_text(), include_str!("../testdata/apply_error.txt"));
+        assert_eq!(
+            apply.to_markdown(),
+            include_str!("../testdata/apply_error.md")
+        );
+    }
+
+    #[test]
+    fn schema_apply_golden() {
+        let apply: SchemaApply =
+            serde_json::from_str(include_str!("../testdata/schema_apply_error.json")).unwrap();
+
+        assert_eq!(
+            apply.to_text(),
+            include_str!("../testdata/schema_apply_error.txt")
+        );
+        assert_eq!(
+            apply.to_markdown(),
+            include_str!("../testdata/schema_apply_error.md")
+        );
+    }
+
+    #[test]
+    fn format_durations() {
+        let tests = [
+            (Duration::ZERO, "0s"),
+            (Duration::nanoseconds(999), "999ns"),
+            (Duration::nanoseconds(1_500), "1.5µs"),
+            (Duration::nanoseconds(999_999), "999.999µs"),
+            (Duration::nanoseconds(1_000_000), "1ms"),
+            (Duration::nanoseconds(10_250_000), "10.25ms"),
+            (Duration::nanoseconds(4_219_400), "4.219ms"),
+            (Duration::nanoseconds(4_219_500), "4.22ms"),
+            (Duration::nanoseconds(999_999_499), "999.999ms"),
+            (Duration::nanoseconds(999_999_999), "1s"),
+            (Duration::milliseconds(1_500), "1.5s"),
+            (Duration::nanoseconds(59_999_999_999), "1m0s"),
+            (Duration::seconds(90), "1m30s"),
+            (Duration::milliseconds(3_723_250), "1h2m3.25s"),
+            (Duration::milliseconds(-1_500), "-1.5s"),
+        ];
+
+        for (d, want) in tests {
+            assert_eq!(format_duration(d), want, "{:?}", d);
+        }
+    }
+
+    #[test]
+    fn code_block_fences() {
+        assert_eq!(code_block("SELECT 1;"), "```sql\nSELECT 1;\n```");
+        assert_eq!(code_block("SELECT '```';"), "````sql\nSELECT '```';\n````");
+        assert_eq!(
+            code_block("SELECT '`', '``', '`````';"),
+            "``````sql\nSELECT '`', '``', '`````';\n``````"
+        );
+    }
+}
@@ -0,0 +1,276 @@
+use anyhow::anyhow;
+use strum::Display;
+
+use crate::atlas::{Client, MigrateDownParams};
+use crate::atlas_models::{File, MigrateDown, MigrationStatus};
+use crate::policy::tokenize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RollbackState {
+    /// The rollback can be run with `migrate_down`.
+    Ready,
+
+    /// The database is already at the target version.
+    NothingToRevert,
+
+    /// Atlas Cloud requires the plan to be approved at `url` before it runs.
+    PendingApproval { url: String },
+
+    /// Atlas planned files to revert without reporting the statements of
+    /// all of them, so whether the rollback loses data cannot be told.
+    Unknown,
+}
+
+/// Why a statement loses data when it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+pub enum DataLoss {
+    #[strum(serialize = "drops a schema")]
+    DropSchema,
+
+    #[strum(serialize = "drops a table")]
+    DropTable,
+
+    #[strum(serialize = "drops a column")]
+    DropColumn,
+
+    #[strum(serialize = "truncates a table")]
+    Truncate,
+
+    #[strum(serialize = "deletes rows")]
+    Delete,
+}
+impl DataLoss {
+    pub fn of(stmt: &str) -> Option<Self> {
+        let tokens = tokenize(stmt);
+        let kw = |i: usize| tokens.get(i).and_then(|t| t.keyword()).unwrap_or_default();
+
+        match (kw(0).as_str(), kw(1).as_str()) {
+            ("DROP", "SCHEMA" | "DATABASE") => Some(DataLoss::DropSchema),
+            ("DROP", "TABLE") => Some(DataLoss::DropTable),
+            ("TRUNCATE", _) => Some(DataLoss::Truncate),
+            ("DELETE", _) => Some(DataLoss::Delete),
+            ("ALTER", "TABLE")
+                if (2..tokens.len()).any(|i| kw(i) == "DROP" && kw(i + 1) == "COLUMN") =>
+            {
+                Some(DataLoss::DropColumn)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedStatement {
+    pub sql: String,
+    pub data_loss: Option<DataLoss>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RollbackFile {
+    pub file: File,
+    /// The statements that revert the file, in the order they run. Empty if
+    /// atlas did not report them, e.g. while the plan awaits approval.
+    pub statements: Vec<PlannedStatement>,
+}
+
+/// A preview of `migrate down`, from a dry run.
+#[derive(Debug, Clone)]
+pub struct RollbackPlan {
+    pub current: String,
+    pub target: String,
+    pub state: RollbackState,
+    /// The files to revert, most recent first.
+    pub files: Vec<RollbackFile>,
+    /// The dry run the plan was made from.
+    pub down: MigrateDown,
+}
+impl RollbackPlan {
+    pub fn from_migrate_down(down: MigrateDown) -> Self {
+        let files = down
+            .planned
+            .iter()
+            .map(|file| RollbackFile {
+                file: file.clone(),
+                statements: down
+                    .reverted
+                    .iter()
+                    .filter(|r| r.file.name == file.name)
+                    .flat_map(|r| r.applied.iter())
+                    .map(|sql| PlannedStatement {
+                        sql: sql.clone(),
+                        data_loss: DataLoss::of(sql),
+                    })
+                    .collect(),
+            })
+            .collect::<Vec<RollbackFile>>();
+
+        let state = if down
+            .status
+            .as_ref()
+            .is_some_and(MigrationStatus::needs_approval)
+        {
+            RollbackState::PendingApproval {
+                url: down.url.clone(),
+            }
+        } else if files.is_empty() {
+            RollbackState::NothingToRevert
+        } else if files.iter().any(|f| f.statements.is_empty()) {
+            RollbackState::Unknown
+        } else {
+            RollbackState::Ready
+        };
+
+        Self {
+            current: down.current.clone(),
+            target: down.target.clone(),
+            state,
+            files,
+            down,
+        }
+    }
+
+    pub fn statements(&self) -> impl Iterator<Item = &PlannedStatement> {
+        self.files.iter().flat_map(|f| f.statements.iter())
+    }
+
+    /// The statements that lose data, with the file each belongs to. Only
+    /// complete if the state is [`RollbackState::Ready`].
+    pub fn data_loss(&self) -> impl Iterator<Item = (&File, &PlannedStatement)> {
+        self.files.iter().flat_map(|f| {
+            f.statements
+                .iter()
+                .filter(|s| s.data_loss.is_some())
+                .map(move |s| (&f.file, s))
+        })
+    }
+
+    /// Reports whether the rollback loses data. A plan whose statements are
+    /// not all known counts as losing data.
+    pub fn loses_data(&self) -> bool {
+        matches!(
+            self.state,
+            RollbackState::Unknown | RollbackState::PendingApproval { .. }
+        ) || self.data_loss().next().is_some()
+    }
+}
+
+impl Client {
+    /// Dry-runs `migrate down` with `params` and returns what it would
+    /// revert. Nothing is reverted, whatever `params.dry_run` says.
+    pub fn plan_rollback(&self, params: MigrateDownParams) -> anyhow::Result<RollbackPlan> {
+        let down = self.migrate_down(MigrateDownParams {
+            dry_run: true,
+            ..params
+        })?;
+
+        if !down.error.is_empty() {
+            return Err(anyhow!("failed to plan rollback: {}", down.error));
+        }
+
+        Ok(RollbackPlan::from_migrate_down(down))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // migrate down --dry-run --format '{{ json . }}' reverting two files
+    const DRY_RUN: &str = r#"{
+        "Planned": [
+            {"Name": "20240510130000_add_email.sql", "Version": "20240510130000", "Description": "add_email"},
+            {"Name": "20240510120000_create_users.sql", "Version": "20240510120000", "Description": "create_users"}
+        ],
+        "Reverted": [
+            {
+                "Name": "20240510130000_add_email.sql",
+                "Version": "20240510130000",
+                "Description": "add_email",
+                "Start": "2024-05-10T15:00:00.000000001+02:00",
+                "End": "2024-05-10T15:00:00.000000002+02:00",
+                "Skipped": 0,
+                "Applied": ["ALTER TABLE \"users\" DROP COLUMN \"email\";"],
+                "Scope": "statement",
+                "Error": null
+            },
+            {
+                "Name": "20240510120000_create_users.sql",
+                "Version": "20240510120000",
+                "Description": "create_users",
+                "Start": "2024-05-10T15:00:00.000000003+02:00",
+                "End": "2024-05-10T15:00:00.000000004+02:00",
+                "Skipped": 0,
+                "Applied": ["DROP TABLE \"users\";"],
+                "Scope": "statement",
+                "Error": null
+            }
+        ],
+        "Current": "20240510130000",
+        "Target": "20240510110000",
+        "Total": 2,
+        "Start": "2024-05-10T15:00:00+02:00",
+        "End": "2024-05-10T15:00:00.1+02:00",
+        "Status": "PENDING"
+    }"#;
+
+    fn plan(json: &str) -> RollbackPlan {
+        RollbackPlan::from_migrate_down(serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn dry_run_reports_data_loss() {
+        let plan = plan(DRY_RUN);
+
+        assert_eq!(plan.state, RollbackState::Ready);
+        assert_eq!(plan.files.len(), 2);
+        assert_eq!(plan.statements().count(), 2);
+
+        let loss = plan
+            .data_loss()
+            .map(|(f, s)| (f.version.as_str(), s.data_loss))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            loss,
+            vec![
+                ("20240510130000", Some(DataLoss::DropColumn)),
+                ("20240510120000", Some(DataLoss::DropTable)),
+            ]
+        );
+        assert!(plan.loses_data());
+    }
+
+    #[test]
+    fn missing_statements_are_unknown() {
+        let mut down: MigrateDown = serde_json::from_str(DRY_RUN).unwrap();
+        down.reverted.truncate(1);
+
+        let plan = RollbackPlan::from_migrate_down(down);
+        assert_eq!(plan.state, RollbackState::Unknown);
+        assert!(plan.loses_data());
+
+        let mut down: MigrateDown = serde_json::from_str(DRY_RUN).unwrap();
+        down.reverted.clear();
+        assert_eq!(
+            RollbackPlan::from_migrate_down(down).state,
+            RollbackState::Unknown
+        );
+    }
+
+    #[test]
+    fn pending_approval_and_nothing_to_revert() {
+        let plan = plan(
+            r#"{"Planned": [{"Name": "2_b.sql", "Version": "2"}], "Current": "2", "Target": "1", "URL": "https://org.atlasgo.cloud/plans/1", "Status": "PENDING_USER"}"#,
+        );
+        assert_eq!(
+            plan.state,
+            RollbackState::PendingApproval {
+                url: "https://org.atlasgo.cloud/plans/1".into()
+            }
+        );
+        assert!(plan.loses_data());
+
+        let nothing = self::plan(r#"{"Current": "1", "Target": "1", "Status": "OK"}"#);
+        assert_eq!(nothing.state, RollbackState::NothingToRevert);
+        assert!(!nothing.loses_data());
+    }
+}
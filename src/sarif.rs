@@ -0,0 +1,371 @@
+use serde::{Deserialize, Serialize};
+
+use crate::atlas_models::{Diagnostic, FileReport, SuggestedFix, SummaryReport};
+use crate::util::{file_path, floor_char_boundary, line_col, local_dir};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const SARIF_VERSION: &str = "2.1.0";
+const ANALYZERS_URI: &str = "https://atlasgo.io/lint/analyzers";
+
+/// A SARIF 2.1.0 log, as consumed by code scanning dashboards. Only the parts
+/// of the format needed to describe atlas lint results are modelled.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SarifLog {
+    #[serde(rename = "$schema")]
+    pub schema: String,
+
+    pub version: String,
+
+    pub runs: Vec<Run>,
+}
+impl SarifLog {
+    /// Converts a `migrate lint` report into a SARIF log with a single run.
+    /// Every diagnostic becomes a result located in its migration file, and
+    /// file and step errors become tool execution notifications.
+    pub fn from_summary_report(report: &SummaryReport) -> Self {
+        let dir = local_dir(&report.env.dir);
+        let mut rules: Vec<ReportingDescriptor> = Vec::new();
+        let mut results = Vec::new();
+        let mut notifications = Vec::new();
+
+        for file in &report.files {
+            let uri = file_path(dir.as_deref(), &file.name);
+
+            for r in &file.reports {
+                for d in &r.diagnostics {
+                    if !d.code.is_empty() && !rules.iter().any(|rule| rule.id == d.code) {
+                        rules.push(ReportingDescriptor {
+                            id: d.code.clone(),
+                            short_description: Some(Message::new(&r.text)),
+                            help_uri: Some(format!("{}#{}", ANALYZERS_URI, d.code)),
+                        });
+                    }
+
+                    // diagnostics without fixes of their own inherit those of
+                    // the report they belong to
+                    let fixes = if d.suggested_fixes.is_empty() {
+                        &r.suggested_fixes
+                    } else {
+                        &d.suggested_fixes
+                    };
+
+                    results.push(SarifResult::from_diagnostic(d, file, &uri, fixes));
+                }
+            }
+
+            if !file.error.is_empty() {
+                notifications.push(Notification {
+                    level: Level::Error,
+                    message: Message::new(&file.error),
+                    locations: vec![Location::file(&uri, None)],
+                });
+            }
+        }
+
+        for step in &report.steps {
+            if !step.error.is_empty() {
+                notifications.push(Notification {
+                    level: Level::Error,
+                    message: Message::new(&format!("{}: {}", step.name, step.error)),
+                    locations: Vec::new(),
+                });
+            }
+        }
+
+        Self {
+            schema: SARIF_SCHEMA.into(),
+            version: SARIF_VERSION.into(),
+            runs: vec![Run {
+                tool: Tool {
+                    driver: ToolComponent {
+                        name: "atlas".into(),
+                        information_uri: Some("https://atlasgo.io".into()),
+                        rules,
+                    },
+                },
+                invocations: vec![Invocation {
+                    execution_successful: notifications.is_empty(),
+                    tool_execution_notifications: notifications,
+                }],
+                results,
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Run {
+    pub tool: Tool,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub invocations: Vec<Invocation>,
+
+    pub results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Tool {
+    pub driver: ToolComponent,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolComponent {
+    pub name: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub information_uri: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<ReportingDescriptor>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportingDescriptor {
+    pub id: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub short_description: Option<Message>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub help_uri: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Level {
+    None,
+    Note,
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rule_id: Option<String>,
+
+    pub level: Level,
+
+    pub message: Message,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<Location>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fixes: Vec<Fix>,
+}
+impl SarifResult {
+    fn from_diagnostic(
+        d: &Diagnostic,
+        file: &FileReport,
+        uri: &str,
+        fixes: &[SuggestedFix],
+    ) -> Self {
+        Self {
+            rule_id: Some(d.code.clone()).filter(|c| !c.is_empty()),
+            level: Level::Warning,
+            message: Message::new(&d.text),
+            locations: vec![Location::file(uri, Some(Region::at(&file.text, d.pos)))],
+            fixes: fixes
+                .iter()
+                .filter_map(|f| Fix::from_suggested_fix(f, uri))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Message {
+    pub text: String,
+}
+impl Message {
+    fn new(text: &str) -> Self {
+        Self { text: text.into() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub physical_location: PhysicalLocation,
+}
+impl Location {
+    fn file(uri: &str, region: Option<Region>) -> Self {
+        Self {
+            physical_location: PhysicalLocation {
+                artifact_location: ArtifactLocation { uri: uri.into() },
+                region,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicalLocation {
+    pub artifact_location: ArtifactLocation,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArtifactLocation {
+    pub uri: String,
+}
+
+/// A region of a file. Lines and columns are 1-based.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Region {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_column: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_line: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub char_offset: Option<usize>,
+}
+impl Region {
+    /// The region starting at byte offset `pos` of `text`. Without the file
+    /// text only the offset is known.
+    fn at(text: &str, pos: isize) -> Self {
+        let offset = usize::try_from(pos).unwrap_or(0);
+
+        if text.is_empty() || offset > text.len() {
+            return Self {
+                char_offset: Some(offset),
+                ..Self::default()
+            };
+        }
+
+        let (line, column) = line_col(text, offset).unwrap_or((1, 1));
+
+        Self {
+            start_line: Some(line),
+            start_column: Some(column),
+            end_line: None,
+            char_offset: Some(text[..floor_char_boundary(text, offset)].chars().count()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fix {
+    pub description: Message,
+
+    pub artifact_changes: Vec<ArtifactChange>,
+}
+impl Fix {
+    /// Suggested fixes without a text edit are advice only and have no SARIF
+    /// counterpart.
+    fn from_suggested_fix(fix: &SuggestedFix, uri: &str) -> Option<Self> {
+        let edit = fix.text_edit.as_ref()?;
+        let start = usize::try_from(edit.line).ok()?;
+        let end = usize::try_from(edit.end).unwrap_or(start).max(start);
+
+        Some(Self {
+            description: Message::new(&fix.message),
+            artifact_changes: vec![ArtifactChange {
+                artifact_location: ArtifactLocation { uri: uri.into() },
+                replacements: vec![Replacement {
+                    deleted_region: Region {
+                        start_line: Some(start),
+                        end_line: Some(end),
+                        ..Region::default()
+                    },
+                    inserted_content: Some(ArtifactContent {
+                        text: edit.new_text.clone(),
+                    }),
+                }],
+            }],
+        })
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtifactChange {
+    pub artifact_location: ArtifactLocation,
+
+    pub replacements: Vec<Replacement>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Replacement {
+    pub deleted_region: Region,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inserted_content: Option<ArtifactContent>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArtifactContent {
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Invocation {
+    pub execution_successful: bool,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_execution_notifications: Vec<Notification>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Notification {
+    pub level: Level,
+
+    pub message: Message,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub locations: Vec<Location>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // migrate lint --format '{{ json . }}' with a multibyte comment, a
+    // report-level fix, an advice-only fix, a file error and a step error
+    const LINT: &str = include_str!("../testdata/lint.json");
+
+    #[test]
+    fn from_summary_report_golden() {
+        let report: SummaryReport = serde_json::from_str(LINT).unwrap();
+        let log = SarifLog::from_summary_report(&report);
+
+        let want: serde_json::Value =
+            serde_json::from_str(include_str!("../testdata/lint.sarif.json")).unwrap();
+        assert_eq!(serde_json::to_value(&log).unwrap(), want);
+    }
+
+    #[test]
+    fn region_counts_characters() {
+        let text = "-- é\nSELECT 1;";
+
+        let region = Region::at(text, 6);
+        assert_eq!(region.start_line, Some(2));
+        assert_eq!(region.start_column, Some(1));
+        assert_eq!(region.char_offset, Some(5));
+
+        // past the end of the text only the offset is known
+        let region = Region::at(text, 100);
+        assert_eq!(region.start_line, None);
+        assert_eq!(region.char_offset, Some(100));
+
+        let region = Region::at("", 12);
+        assert_eq!(region.start_line, None);
+        assert_eq!(region.char_offset, Some(12));
+    }
+}
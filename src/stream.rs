@@ -0,0 +1,751 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use std::process::{Child, ExitStatus};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use anyhow::anyhow;
+use time::OffsetDateTime;
+
+use crate::atlas::{migrate_apply_args, Client, MigrateApplyParams};
+use crate::atlas_models::{
+    AppliedFile, File, MigrateApply, MigrateApplyError, SqlError, GO_ZERO_TIME,
+};
+use crate::util::local_dir;
+
+/// Progress of a streaming `migrate apply`, parsed from the text atlas
+/// prints while it runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    /// Atlas planned the run. `current` is empty for a fresh database.
+    Started {
+        current: String,
+        target: String,
+        total: usize,
+    },
+
+    FileStarted {
+        version: String,
+    },
+
+    /// A statement of the file ran. Atlas prints a statement before running
+    /// it, so this is sent once the next line of output arrives.
+    StatementExecuted {
+        version: String,
+        sql: String,
+    },
+
+    /// All statements of the file ran. `duration` is measured locally.
+    FileFinished {
+        version: String,
+        duration: Duration,
+    },
+
+    /// The run failed, in the statement `sql` of the file `version` if the
+    /// failure happened while applying one.
+    Error {
+        version: Option<String>,
+        sql: Option<String>,
+        message: String,
+    },
+
+    /// Any other line of output, e.g. the summary atlas prints at the end.
+    Output {
+        line: String,
+        stderr: bool,
+    },
+}
+
+impl Client {
+    /// Runs `migrate apply` like [`Client::migrate_apply`], but reads the
+    /// output of atlas as it arrives and calls `on_event` for each step, so
+    /// long migrations can report progress. The callback runs on the calling
+    /// thread; forward the events to a channel to consume them elsewhere.
+    ///
+    /// The returned result is rebuilt from the text output, since atlas only
+    /// prints progress in that form, and differs from the one
+    /// [`Client::migrate_apply`] returns:
+    ///
+    /// - timestamps are taken locally as the output arrives;
+    /// - `skipped` is always zero;
+    /// - files are looked up in `params.dir_url` (default `file://migrations`)
+    ///   when it is local. `pending` lists the files between the current and
+    ///   target version found there; if the dir cannot be read, or its files
+    ///   do not add up to the total atlas announced, it only lists the files
+    ///   that started, and their names and descriptions are empty.
+    ///
+    /// If `on_event` panics, atlas is killed before the panic propagates.
+    pub fn migrate_apply_streaming<F>(
+        &self,
+        params: MigrateApplyParams,
+        mut on_event: F,
+    ) -> anyhow::Result<MigrateApply>
+    where
+        F: FnMut(&ProgressEvent),
+    {
+        let args = migrate_apply_args(&params)?;
+        let mut child = self
+            .command(args.iter().map(String::as_str).collect())
+            .spawn()
+            .map_err(|e| anyhow!("failed to run cmd: {}", e))?;
+
+        let (tx, rx) = mpsc::channel();
+        let readers = [
+            child
+                .stdout
+                .take()
+                .map(|out| read_lines(out, false, tx.clone())),
+            child
+                .stderr
+                .take()
+                .map(|err| read_lines(err, true, tx.clone())),
+        ];
+        drop(tx);
+
+        let mut child = ChildGuard(Some(child));
+
+        let dir = params
+            .dir_url
+            .as_ref()
+            .map(|url| url.as_str())
+            .unwrap_or("file://migrations");
+        let mut run = Run::new(self.dir_files(dir));
+        let mut stderr = Vec::new();
+
+        for (line, is_stderr) in rx {
+            let line = strip_ansi(&line);
+            if is_stderr {
+                stderr.push(line.clone());
+            }
+
+            for event in run.line(&line, is_stderr) {
+                on_event(&event);
+            }
+        }
+
+        for reader in readers.into_iter().flatten() {
+            let _ = reader.join();
+        }
+
+        let status = child
+            .wait()
+            .map_err(|e| anyhow!("failed to wait for cmd: {}", e))?;
+
+        for event in run.finish(status.success()) {
+            on_event(&event);
+        }
+
+        let apply = run.apply;
+
+        if !status.success() {
+            if !apply.error.is_empty() {
+                return Err(MigrateApplyError::new(vec![apply]).into());
+            }
+
+            return Err(anyhow!(
+                "cmd had non-zero exit status {}: {}",
+                status,
+                stderr.join("\n").trim(),
+            ));
+        }
+
+        Ok(apply)
+    }
+
+    /// The migration files of a local dir url by version, relative to the
+    /// working dir. Empty if the dir is not local or cannot be read.
+    fn dir_files(&self, dir_url: &str) -> HashMap<String, File> {
+        let dir = match local_dir(dir_url) {
+            Some(dir) => dir,
+            None => return HashMap::new(),
+        };
+
+        let path = match self.working_dir() {
+            Some(wd) => Path::new(wd).join(dir),
+            None => Path::new(&dir).to_path_buf(),
+        };
+
+        let entries = match std::fs::read_dir(path) {
+            Ok(entries) => entries,
+            Err(_) => return HashMap::new(),
+        };
+
+        entries
+            .flatten()
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter(|name| name.ends_with(".sql"))
+            .map(|name| {
+                let file = File::from_name(&name);
+                (file.version.clone(), file)
+            })
+            .collect()
+    }
+}
+
+/// Kills and reaps atlas unless it was waited for, so a run abandoned by a
+/// panic does not leave it running.
+struct ChildGuard(Option<Child>);
+impl ChildGuard {
+    fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        match self.0.take() {
+            Some(mut child) => child.wait(),
+            None => Err(std::io::Error::other("process already waited for")),
+        }
+    }
+}
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(mut child) = self.0.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn read_lines<R>(
+    out: R,
+    stderr: bool,
+    tx: mpsc::Sender<(String, bool)>,
+) -> std::thread::JoinHandle<()>
+where
+    R: Read + Send + 'static,
+{
+    std::thread::spawn(move || {
+        for line in BufReader::new(out).lines().map_while(Result::ok) {
+            if tx.send((line, stderr)).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// Removes the color codes atlas adds when it believes it writes to a
+/// terminal.
+fn strip_ansi(line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c == '\u{1b}' {
+            if chars.next() == Some('[') {
+                for c in chars.by_ref() {
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+
+        out.push(c);
+    }
+
+    out
+}
+
+/// The state of the output parser: the result so far and the statement
+/// atlas printed last, which runs until the next line arrives.
+struct Run {
+    files: HashMap<String, File>,
+    apply: MigrateApply,
+    file_start: Option<Instant>,
+    stmt: Option<String>,
+    summary: bool,
+    fatal: Option<String>,
+    /// Whether `apply.pending` was filled from the migration dir.
+    pending_known: bool,
+}
+impl Run {
+    fn new(files: HashMap<String, File>) -> Self {
+        Self {
+            files,
+            apply: MigrateApply {
+                pending: vec![],
+                applied: vec![],
+                current: String::new(),
+                target: String::new(),
+                start: OffsetDateTime::now_utc(),
+                end: GO_ZERO_TIME,
+                error: String::new(),
+            },
+            file_start: None,
+            stmt: None,
+            summary: false,
+            fatal: None,
+            pending_known: false,
+        }
+    }
+
+    fn version(&self) -> Option<String> {
+        match self.file_start {
+            Some(_) => self.apply.applied.last().map(|f| f.file.version.clone()),
+            None => None,
+        }
+    }
+
+    fn line(&mut self, line: &str, stderr: bool) -> Vec<ProgressEvent> {
+        let trimmed = line.trim();
+        let mut events = vec![];
+
+        if stderr {
+            // stderr is read separately and may run ahead of stdout, so the
+            // error atlas exits with is only used if stdout reported none
+            if let Some(message) = trimmed.strip_prefix("Error:") {
+                self.fatal.get_or_insert_with(|| message.trim().into());
+            } else if !trimmed.is_empty() {
+                events.push(ProgressEvent::Output {
+                    line: line.into(),
+                    stderr,
+                });
+            }
+            return events;
+        }
+
+        if let Some(rest) = trimmed.strip_prefix("Migrating to version ") {
+            if let Some(event) = self.started(rest) {
+                events.push(event);
+                return events;
+            }
+        }
+
+        if let Some(version) = trimmed.strip_prefix("-- migrating version ") {
+            events.extend(self.finish_file());
+            events.push(self.file_started(version.trim()));
+        } else if let Some(sql) = trimmed.strip_prefix("-> ") {
+            events.extend(self.flush_stmt());
+            self.stmt = Some(sql.into());
+        } else if trimmed.starts_with("-- ok") {
+            events.extend(self.finish_file());
+        } else if let Some(message) = trimmed.strip_prefix("Error:") {
+            events.extend(self.error(message.trim()));
+        } else if trimmed.starts_with("-------") {
+            events.extend(self.flush_stmt());
+            self.summary = true;
+        } else if let Some(stmt) = self.stmt.as_mut().filter(|_| !self.summary) {
+            // continuation of a statement spanning several lines
+            stmt.push('\n');
+            stmt.push_str(line);
+        } else if !trimmed.is_empty() {
+            events.push(ProgressEvent::Output {
+                line: line.into(),
+                stderr,
+            });
+        }
+
+        events
+    }
+
+    /// Parses `<target>[ from <current>] (<n> migrations in total):`.
+    fn started(&mut self, rest: &str) -> Option<ProgressEvent> {
+        let (versions, total) = rest.split_once(" (")?;
+        let total = total.split_whitespace().next()?.parse::<usize>().ok()?;
+
+        let (target, current) = match versions.split_once(" from ") {
+            Some((target, current)) => (target, current),
+            None => (versions, ""),
+        };
+
+        self.apply.target = target.trim().into();
+        self.apply.current = current.trim().into();
+
+        let mut pending = self
+            .files
+            .values()
+            .filter(|f| {
+                f.version.as_str() > self.apply.current.as_str()
+                    && f.version.as_str() <= self.apply.target.as_str()
+            })
+            .cloned()
+            .collect::<Vec<File>>();
+        pending.sort_by(|a, b| a.version.cmp(&b.version));
+
+        if pending.len() == total {
+            self.apply.pending = pending;
+            self.pending_known = true;
+        }
+
+        Some(ProgressEvent::Started {
+            current: self.apply.current.clone(),
+            target: self.apply.target.clone(),
+            total,
+        })
+    }
+
+    fn file_started(&mut self, version: &str) -> ProgressEvent {
+        let file = self.files.get(version).cloned().unwrap_or(File {
+            version: version.into(),
+            ..Default::default()
+        });
+
+        if !self.pending_known {
+            self.apply.pending.push(file.clone());
+        }
+        self.apply.applied.push(AppliedFile {
+            file,
+            start: OffsetDateTime::now_utc(),
+            end: GO_ZERO_TIME,
+            skipped: 0,
+            applied: vec![],
+            error: None,
+        });
+        self.file_start = Some(Instant::now());
+
+        ProgressEvent::FileStarted {
+            version: version.into(),
+        }
+    }
+
+    fn flush_stmt(&mut self) -> Option<ProgressEvent> {
+        let sql = self.stmt.take()?;
+        let version = self.version()?;
+
+        if let Some(file) = self.apply.applied.last_mut() {
+            file.applied.push(sql.clone());
+        }
+
+        Some(ProgressEvent::StatementExecuted { version, sql })
+    }
+
+    /// Ends the current file, reporting its last statement first.
+    fn finish_file(&mut self) -> Vec<ProgressEvent> {
+        let mut events = self.flush_stmt().into_iter().collect::<Vec<_>>();
+
+        if let (Some(started), Some(file)) = (self.file_start.take(), self.apply.applied.last_mut())
+        {
+            file.end = OffsetDateTime::now_utc();
+            events.push(ProgressEvent::FileFinished {
+                version: file.file.version.clone(),
+                duration: started.elapsed(),
+            });
+        }
+
+        events
+    }
+
+    /// Records an error. The statement printed last is the one that failed.
+    fn error(&mut self, message: &str) -> Option<ProgressEvent> {
+        if !self.apply.error.is_empty() {
+            return None;
+        }
+
+        let version = self.version();
+        let sql = self.stmt.take();
+
+        if let Some(file) = self.apply.applied.last_mut().filter(|_| version.is_some()) {
+            if let Some(ref sql) = sql {
+                file.applied.push(sql.clone());
+            }
+            file.end = OffsetDateTime::now_utc();
+            file.error = Some(SqlError {
+                sql: sql.clone().unwrap_or_default(),
+                error: message.into(),
+            });
+        }
+
+        self.file_start = None;
+        self.apply.error = message.into();
+
+        Some(ProgressEvent::Error {
+            version,
+            sql,
+            message: message.into(),
+        })
+    }
+
+    /// Completes the run once atlas exited. A file still running when atlas
+    /// failed is the one that failed, in the statement printed last.
+    fn finish(&mut self, success: bool) -> Vec<ProgressEvent> {
+        let mut events = vec![];
+
+        match self.fatal.take() {
+            Some(message) if !success => events.extend(self.error(&message)),
+            _ => events.extend(self.finish_file()),
+        }
+        self.apply.end = OffsetDateTime::now_utc();
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds `lines` to a run over `files`, then finishes it. Durations are
+    /// zeroed so events can be compared.
+    fn parse(files: &[&str], lines: &[(&str, bool)], success: bool) -> (Vec<ProgressEvent>, Run) {
+        let files = files
+            .iter()
+            .map(|name| {
+                let file = File::from_name(name);
+                (file.version.clone(), file)
+            })
+            .collect();
+
+        let mut run = Run::new(files);
+        let mut events = Vec::new();
+
+        for (line, stderr) in lines {
+            events.extend(run.line(&strip_ansi(line), *stderr));
+        }
+        events.extend(run.finish(success));
+
+        let events = events
+            .into_iter()
+            .map(|e| match e {
+                ProgressEvent::FileFinished { version, .. } => ProgressEvent::FileFinished {
+                    version,
+                    duration: Duration::ZERO,
+                },
+                e => e,
+            })
+            .collect();
+
+        (events, run)
+    }
+
+    fn stdout(lines: &str) -> Vec<(&str, bool)> {
+        lines.lines().map(|l| (l, false)).collect()
+    }
+
+    fn started(version: &str) -> ProgressEvent {
+        ProgressEvent::FileStarted {
+            version: version.into(),
+        }
+    }
+
+    fn executed(version: &str, sql: &str) -> ProgressEvent {
+        ProgressEvent::StatementExecuted {
+            version: version.into(),
+            sql: sql.into(),
+        }
+    }
+
+    fn finished(version: &str) -> ProgressEvent {
+        ProgressEvent::FileFinished {
+            version: version.into(),
+            duration: Duration::ZERO,
+        }
+    }
+
+    fn output(line: &str) -> ProgressEvent {
+        ProgressEvent::Output {
+            line: line.into(),
+            stderr: false,
+        }
+    }
+
+    const FILES: [&str; 3] = [
+        "20240510120000_create_users.sql",
+        "20240510130000_add_email.sql",
+        "20240510140000_add_index.sql",
+    ];
+
+    #[test]
+    fn fresh_database() {
+        let out = "Migrating to version 20240510130000 (2 migrations in total):
+
+  -- migrating version 20240510120000
+    -> CREATE TABLE \"users\" (\"id\" bigint NOT NULL, PRIMARY KEY (\"id\"));
+  -- ok (4.219ms)
+
+  -- migrating version 20240510130000
+    -> ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;
+  -- ok (1.052ms)
+
+  -------------------------
+  -- 8.937ms
+  -- 2 migrations
+  -- 2 sql statements
+";
+        let (events, run) = parse(&FILES, &stdout(out), true);
+
+        assert_eq!(
+            events,
+            vec![
+                ProgressEvent::Started {
+                    current: String::new(),
+                    target: "20240510130000".into(),
+                    total: 2,
+                },
+                started("20240510120000"),
+                executed(
+                    "20240510120000",
+                    "CREATE TABLE \"users\" (\"id\" bigint NOT NULL, PRIMARY KEY (\"id\"));"
+                ),
+                finished("20240510120000"),
+                started("20240510130000"),
+                executed(
+                    "20240510130000",
+                    "ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;"
+                ),
+                finished("20240510130000"),
+                output("  -- 8.937ms"),
+                output("  -- 2 migrations"),
+                output("  -- 2 sql statements"),
+            ]
+        );
+
+        let apply = run.apply;
+        assert_eq!(apply.current, "");
+        assert_eq!(apply.target, "20240510130000");
+        assert!(apply.error.is_empty());
+        assert_eq!(
+            apply
+                .pending
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>(),
+            &FILES[..2]
+        );
+        assert_eq!(apply.applied.len(), 2);
+        assert_eq!(apply.applied[1].file.description, "add_email");
+        assert!(apply.applied.iter().all(|f| f.error.is_none()));
+    }
+
+    #[test]
+    fn multi_line_statement() {
+        let out = "\u{1b}[1mMigrating to version 20240510140000 from 20240510130000 (1 migrations in total):\u{1b}[0m
+
+  -- migrating version 20240510140000
+    -> CREATE FUNCTION \"touch\"() RETURNS trigger AS $$
+      BEGIN
+        NEW.updated_at = now();
+        RETURN NEW;
+      END;
+      $$ LANGUAGE plpgsql;
+    -> CREATE INDEX \"users_email\" ON \"users\" (\"email\");
+  -- ok (12.5ms)
+";
+        let (events, run) = parse(&FILES, &stdout(out), true);
+
+        assert_eq!(
+            events[..4],
+            [
+                ProgressEvent::Started {
+                    current: "20240510130000".into(),
+                    target: "20240510140000".into(),
+                    total: 1,
+                },
+                started("20240510140000"),
+                executed(
+                    "20240510140000",
+                    "CREATE FUNCTION \"touch\"() RETURNS trigger AS $$\n      BEGIN\n        NEW.updated_at = now();\n        RETURN NEW;\n      END;\n      $$ LANGUAGE plpgsql;"
+                ),
+                executed(
+                    "20240510140000",
+                    "CREATE INDEX \"users_email\" ON \"users\" (\"email\");"
+                ),
+            ]
+        );
+        assert_eq!(events[4], finished("20240510140000"));
+        assert_eq!(run.apply.applied[0].applied.len(), 2);
+        assert_eq!(run.apply.pending.len(), 1);
+    }
+
+    #[test]
+    fn error_partway_through_file() {
+        let out = "Migrating to version 20240510140000 from 20240510120000 (2 migrations in total):
+
+  -- migrating version 20240510130000
+    -> ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;
+  -- ok (1.052ms)
+
+  -- migrating version 20240510140000
+    -> CREATE INDEX \"users_email\" ON \"users\" (\"email\");
+    -> CREATE UNIQUE INDEX \"users_email\" ON \"users\" (\"email\");
+    Error: pq: relation \"users_email\" already exists
+  -------------------------
+  -- 6.102ms
+  -- 1 migration ok, 1 with errors
+  -- 2 sql statements ok, 1 with errors
+";
+        let mut lines = stdout(out);
+        lines.push((
+            "Error: sql/migrate: executing statement \"CREATE UNIQUE INDEX ...\": pq: relation \"users_email\" already exists",
+            true,
+        ));
+
+        let (events, run) = parse(&FILES, &lines, false);
+
+        let message = "pq: relation \"users_email\" already exists";
+        assert_eq!(
+            events[4..8],
+            [
+                started("20240510140000"),
+                executed(
+                    "20240510140000",
+                    "CREATE INDEX \"users_email\" ON \"users\" (\"email\");"
+                ),
+                ProgressEvent::Error {
+                    version: Some("20240510140000".into()),
+                    sql: Some(
+                        "CREATE UNIQUE INDEX \"users_email\" ON \"users\" (\"email\");".into()
+                    ),
+                    message: message.into(),
+                },
+                output("  -- 6.102ms"),
+            ]
+        );
+        assert!(!events.contains(&finished("20240510140000")));
+
+        let apply = run.apply;
+        assert_eq!(apply.error, message);
+        // the whole run was pending, not only the files that started
+        assert_eq!(apply.pending.len(), 2);
+
+        let failed = &apply.applied[1];
+        assert_eq!(failed.applied.len(), 2);
+        assert_eq!(
+            failed.error.as_ref().map(|e| e.error.as_str()),
+            Some(message)
+        );
+    }
+
+    #[test]
+    fn stderr_error_before_stdout() {
+        let out = "Migrating to version 20240510130000 from 20240510120000 (1 migrations in total):
+
+  -- migrating version 20240510130000
+    -> ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;
+";
+        // atlas exited on a lost connection; stderr was read first
+        let mut lines = vec![("Error: driver: bad connection", true)];
+        lines.extend(stdout(out));
+
+        let (events, run) = parse(&FILES, &lines, false);
+
+        assert_eq!(
+            events[1..],
+            [
+                started("20240510130000"),
+                ProgressEvent::Error {
+                    version: Some("20240510130000".into()),
+                    sql: Some("ALTER TABLE \"users\" ADD COLUMN \"email\" text NULL;".into()),
+                    message: "driver: bad connection".into(),
+                },
+            ]
+        );
+        assert_eq!(run.apply.error, "driver: bad connection");
+
+        // an error on stderr does not fail a run that succeeded
+        let (events, run) = parse(&FILES, &lines, true);
+        assert!(run.apply.error.is_empty());
+        assert_eq!(events.last(), Some(&finished("20240510130000")));
+    }
+
+    #[test]
+    fn unknown_dir_lists_started_files() {
+        let out = "Migrating to version 20240510130000 from 20240510120000 (1 migrations in total):
+  -- migrating version 20240510130000
+    -> SELECT 1;
+  -- ok (1ms)
+";
+        let (_, run) = parse(&[], &stdout(out), true);
+
+        assert_eq!(run.apply.pending.len(), 1);
+        assert_eq!(run.apply.pending[0].version, "20240510130000");
+        assert_eq!(run.apply.pending[0].name, "");
+    }
+}
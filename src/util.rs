@@ -20,6 +20,11 @@ impl NonEmptyString {
         &self.inner
     }
 }
+impl AsRef<str> for NonEmptyString {
+    fn as_ref(&self) -> &str {
+        &self.inner
+    }
+}
 impl std::fmt::Display for NonEmptyString {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.inner)
@@ -76,3 +81,41 @@ impl<'de> Deserialize<'de> for NonEmptyString {
         deserializer.deserialize_string(NonEmptyStringVisitor)
     }
 }
+
+/// Returns the 1-based line and column (counted in characters) of byte
+/// offset `pos` in `text`, or `None` if `pos` is past the end of the text.
+pub fn line_col(text: &str, pos: usize) -> Option<(usize, usize)> {
+    if pos > text.len() {
+        return None;
+    }
+
+    let before = &text[..floor_char_boundary(text, pos)];
+    let line = before.matches('\n').count() + 1;
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+
+    Some((line, before[line_start..].chars().count() + 1))
+}
+
+pub(crate) fn floor_char_boundary(s: &str, mut i: usize) -> usize {
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+
+    i
+}
+
+/// The local path of a `file://` directory url such as `file://migrations`.
+pub(crate) fn local_dir(dir_url: &str) -> Option<String> {
+    let path = dir_url.strip_prefix("file://")?;
+    let path = path.split('?').next().unwrap_or(path);
+
+    Some(path.trim_end_matches('/').to_string())
+}
+
+/// The path of the file `name` in `dir`, or just `name` without a dir.
+pub(crate) fn file_path(dir: Option<&str>, name: &str) -> String {
+    match dir {
+        Some(dir) if !dir.is_empty() => format!("{}/{}", dir, name),
+        _ => name.into(),
+    }
+}